@@ -14,6 +14,9 @@ use tokio_stream::wrappers::ReceiverStream;
 use fs2db::tonic;
 use tonic::{Request, Response, Status};
 
+use fs2db::input::conv::lines::FsSource;
+use fs2db::input::conv::rooted::{rooted_fs_new, RootedFs, RootedSymlinks};
+
 use fs2db::rpc::fs2db::proto::source;
 use source::v1::select_service_server::SelectService;
 use source::v1::{AllRequest, AllResponse, InputBucket};
@@ -39,14 +42,15 @@ pub async fn path2rows(p: &Path) -> Result<impl Stream<Item = Result<Record, Sta
 }
 
 pub struct FsSvc {
-    root: PathBuf,
+    fs: RootedFs<String>,
 }
 
 impl FsSvc {
     pub fn new_env(key: &str) -> Result<Self, Status> {
         let root: String = std::env::var(key)
             .map_err(|e| Status::invalid_argument(format!("root path unknown: {e}")))?;
-        Ok(Self { root: root.into() })
+        let fs = rooted_fs_new(root, RootedSymlinks::WithinRoot)?;
+        Ok(Self { fs })
     }
 
     pub fn new_default() -> Result<Self, Status> {
@@ -58,21 +62,31 @@ impl FsSvc {
 impl SelectService for FsSvc {
     type AllStream = ReceiverStream<Result<AllResponse, Status>>;
 
+    /// Gets all rows of a file.
+    ///
+    /// Resuming is not supported(keys are not sorted); a non-empty start_after is rejected.
     async fn all(&self, req: Request<AllRequest>) -> Result<Response<Self::AllStream>, Status> {
         let ar: AllRequest = req.into_inner();
+        if !ar.start_after.is_empty() {
+            return Err(Status::unimplemented("start_after not supported"));
+        }
         let bkt: InputBucket = ar
             .bkt
             .ok_or_else(|| Status::invalid_argument("input bucket missing"))?;
         let raw_bkt: Vec<u8> = bkt.bucket;
         let basename: String = String::from_utf8(raw_bkt)
             .map_err(|e| Status::invalid_argument(format!("invalid bucket name: {e}")))?;
-        let fullname = self.root.join(basename);
+        let fullname: PathBuf = self.fs.bucket2path(basename)?;
         let rows = path2rows(&fullname).await?;
         let pairs = rows.map(|r| r.map(|row: Record| row.into_pair()));
         let response = pairs.map(|r| {
             r.map(|pair| {
                 let (key, val) = pair;
-                AllResponse { key, val }
+                AllResponse {
+                    key,
+                    val,
+                    rejected: None,
+                }
             })
         });
         let mapd = response.map(Result::Ok::<_, Status>);
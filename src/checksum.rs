@@ -0,0 +1,94 @@
+//! Checks(e.g, checksums) which can be used to verify migrated values
+
+/// Computes a check of a value
+pub trait Checksum: Send + Sync + 'static {
+    fn check(&self, val: &[u8]) -> Vec<u8>;
+}
+
+/// Computes no check(an empty check)
+#[derive(Clone, Copy, Default)]
+pub struct NoCheck {}
+
+impl Checksum for NoCheck {
+    fn check(&self, _val: &[u8]) -> Vec<u8> {
+        vec![]
+    }
+}
+
+/// CRC-32C(Castagnoli) in big endian(4 bytes)
+#[cfg(feature = "checksum_crc32c")]
+#[derive(Clone, Copy, Default)]
+pub struct Crc32c {}
+
+#[cfg(feature = "checksum_crc32c")]
+impl Checksum for Crc32c {
+    fn check(&self, val: &[u8]) -> Vec<u8> {
+        crc32c::crc32c(val).to_be_bytes().into()
+    }
+}
+
+/// xxHash64 in big endian(8 bytes)
+#[cfg(feature = "checksum_xxh64")]
+#[derive(Clone, Copy, Default)]
+pub struct XxHash64 {
+    pub seed: u64,
+}
+
+#[cfg(feature = "checksum_xxh64")]
+impl Checksum for XxHash64 {
+    fn check(&self, val: &[u8]) -> Vec<u8> {
+        xxhash_rust::xxh64::xxh64(val, self.seed)
+            .to_be_bytes()
+            .into()
+    }
+}
+
+/// SHA-256(32 bytes)
+#[cfg(feature = "checksum_sha256")]
+#[derive(Clone, Copy, Default)]
+pub struct Sha256 {}
+
+#[cfg(feature = "checksum_sha256")]
+impl Checksum for Sha256 {
+    fn check(&self, val: &[u8]) -> Vec<u8> {
+        use sha2::Digest;
+        sha2::Sha256::digest(val).to_vec()
+    }
+}
+
+#[cfg(test)]
+mod test_checksum {
+    #[cfg(feature = "checksum_crc32c")]
+    mod crc32c {
+        use crate::checksum::{Checksum, Crc32c};
+
+        #[test]
+        fn check_value() {
+            let chk: Vec<u8> = Crc32c {}.check(b"123456789");
+            assert_eq!(chk, vec![0xe3, 0x06, 0x92, 0x83]);
+        }
+    }
+
+    #[cfg(feature = "checksum_xxh64")]
+    mod xxh64 {
+        use crate::checksum::{Checksum, XxHash64};
+
+        #[test]
+        fn empty() {
+            let chk: Vec<u8> = XxHash64 { seed: 0 }.check(b"");
+            assert_eq!(chk, 0xef46db3751d8e999_u64.to_be_bytes().to_vec());
+        }
+    }
+
+    #[cfg(feature = "checksum_sha256")]
+    mod sha256 {
+        use crate::checksum::{Checksum, Sha256};
+
+        #[test]
+        fn abc() {
+            let chk: Vec<u8> = Sha256 {}.check(b"abc");
+            assert_eq!(&chk[..4], &[0xba, 0x78, 0x16, 0xbf]);
+            assert_eq!(chk.len(), 32);
+        }
+    }
+}
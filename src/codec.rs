@@ -0,0 +1,174 @@
+//! Converts buckets/keys/values from/to bytes(e.g, to serve them over gRPC)
+
+use core::marker::PhantomData;
+
+use tonic::Status;
+
+/// Decodes bytes
+pub trait Decoder: Send + Sync + 'static {
+    type T: Send + Sync;
+
+    fn decode(&self, raw: Vec<u8>) -> Result<Self::T, Status>;
+}
+
+/// Encodes a value to bytes
+pub trait Encoder: Send + Sync + 'static {
+    type T: Send + Sync;
+
+    fn encode(&self, t: Self::T) -> Result<Vec<u8>, Status>;
+}
+
+/// Bytes as is
+#[derive(Clone, Copy, Default)]
+pub struct Raw {}
+
+impl Decoder for Raw {
+    type T = Vec<u8>;
+
+    fn decode(&self, raw: Vec<u8>) -> Result<Self::T, Status> {
+        Ok(raw)
+    }
+}
+
+impl Encoder for Raw {
+    type T = Vec<u8>;
+
+    fn encode(&self, t: Self::T) -> Result<Vec<u8>, Status> {
+        Ok(t)
+    }
+}
+
+/// UTF-8 string
+#[derive(Clone, Copy, Default)]
+pub struct Utf8 {}
+
+impl Decoder for Utf8 {
+    type T = String;
+
+    fn decode(&self, raw: Vec<u8>) -> Result<Self::T, Status> {
+        String::from_utf8(raw).map_err(|e| Status::invalid_argument(format!("invalid utf8: {e}")))
+    }
+}
+
+impl Encoder for Utf8 {
+    type T = String;
+
+    fn encode(&self, t: Self::T) -> Result<Vec<u8>, Status> {
+        Ok(t.into())
+    }
+}
+
+/// Big endian unsigned integer(the byte order of encoded keys matches the numeric order)
+pub struct BigEndian<T> {
+    _t: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for BigEndian<T> {
+    fn default() -> Self {
+        Self { _t: PhantomData }
+    }
+}
+
+impl<T> Clone for BigEndian<T> {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+macro_rules! big_endian_impl {
+    ($int: ty) => {
+        impl Decoder for BigEndian<$int> {
+            type T = $int;
+
+            fn decode(&self, raw: Vec<u8>) -> Result<Self::T, Status> {
+                let a: [u8; core::mem::size_of::<$int>()] =
+                    raw.try_into().map_err(|v: Vec<u8>| {
+                        Status::invalid_argument(format!("invalid integer length: {}", v.len()))
+                    })?;
+                Ok(<$int>::from_be_bytes(a))
+            }
+        }
+
+        impl Encoder for BigEndian<$int> {
+            type T = $int;
+
+            fn encode(&self, t: Self::T) -> Result<Vec<u8>, Status> {
+                Ok(t.to_be_bytes().into())
+            }
+        }
+    };
+}
+
+macro_rules! big_endian_impl_many {
+    ($($int: ty)*) => ($(
+        big_endian_impl!($int);
+    )*)
+}
+
+big_endian_impl_many!(u8 u16 u32 u64 u128);
+
+/// Encodes usize as u64(e.g, line numbers)
+impl Encoder for BigEndian<usize> {
+    type T = usize;
+
+    fn encode(&self, t: Self::T) -> Result<Vec<u8>, Status> {
+        let u: u64 = t as u64;
+        Ok(u.to_be_bytes().into())
+    }
+}
+
+impl Decoder for BigEndian<usize> {
+    type T = usize;
+
+    fn decode(&self, raw: Vec<u8>) -> Result<Self::T, Status> {
+        let u: u64 = BigEndian::<u64>::default().decode(raw)?;
+        usize::try_from(u).map_err(|e| Status::invalid_argument(format!("invalid usize: {e}")))
+    }
+}
+
+/// Decodes a key/val pair(e.g, a row received over gRPC)
+pub trait PairDecoder: Send + Sync + 'static {
+    type T: Send + Sync;
+
+    /// Decodes a row from a key/val pair and an optional check(e.g, checksum)
+    fn decode(&self, key: Vec<u8>, val: Vec<u8>, check: Vec<u8>) -> Result<Self::T, Status>;
+}
+
+/// Decodes a key and a val using [`Decoder`]s(the check is ignored)
+#[derive(Clone, Copy, Default)]
+pub struct Pair<KD, VD> {
+    pub key: KD,
+    pub val: VD,
+}
+
+impl<KD, VD> PairDecoder for Pair<KD, VD>
+where
+    KD: Decoder,
+    VD: Decoder,
+{
+    type T = (KD::T, VD::T);
+
+    fn decode(&self, key: Vec<u8>, val: Vec<u8>, _check: Vec<u8>) -> Result<Self::T, Status> {
+        let k: KD::T = self.key.decode(key)?;
+        let v: VD::T = self.val.decode(val)?;
+        Ok((k, v))
+    }
+}
+
+/// Keeps the check with the decoded row(e.g, to store the checksum sent by a source)
+#[derive(Clone, Copy, Default)]
+pub struct WithCheck<P> {
+    pub row: P,
+}
+
+impl<P> PairDecoder for WithCheck<P>
+where
+    P: PairDecoder,
+{
+    type T = (P::T, Vec<u8>);
+
+    fn decode(&self, key: Vec<u8>, val: Vec<u8>, check: Vec<u8>) -> Result<Self::T, Status> {
+        let row: P::T = self.row.decode(key, val, vec![])?;
+        Ok((row, check))
+    }
+}
@@ -1,4 +1,11 @@
 pub mod s2t;
 
+pub mod checkpoint;
+
+pub mod verify;
+
 #[cfg(feature = "grpc_tonic")]
 pub mod rpc;
+
+#[cfg(all(feature = "json", feature = "async_tokio"))]
+pub mod reconcile;
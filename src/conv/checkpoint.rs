@@ -0,0 +1,19 @@
+//! Checkpoints to resume an interrupted migration
+
+use tonic::Status;
+
+#[cfg(feature = "checkpoint_fs")]
+pub mod fs;
+
+/// Stores the last acknowledged key of an (input bucket, output bucket) pair
+#[tonic::async_trait]
+pub trait Checkpoint: Send + Sync + 'static {
+    /// Gets the last acknowledged key(None: no key acknowledged yet)
+    async fn last_key(&self, ibkt: &[u8], obkt: &[u8]) -> Result<Option<Vec<u8>>, Status>;
+
+    /// Saves the last acknowledged key
+    async fn save(&self, ibkt: &[u8], obkt: &[u8], key: &[u8]) -> Result<(), Status>;
+
+    /// Removes the checkpoint(e.g, after a completed migration)
+    async fn clear(&self, ibkt: &[u8], obkt: &[u8]) -> Result<(), Status>;
+}
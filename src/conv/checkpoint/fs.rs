@@ -0,0 +1,127 @@
+//! Stores checkpoints in a directory(a file per bucket pair)
+
+use std::io;
+use std::path::PathBuf;
+
+use tonic::Status;
+
+use sha2::{Digest, Sha256};
+
+use crate::conv::checkpoint::Checkpoint;
+use crate::hex;
+
+/// Saves the last key of each bucket pair in a file.
+///
+/// The filename is the hex encoded SHA-256 of the bucket pair(`<sha256>.ckpt`);
+/// its length does not depend on the lengths of the buckets.
+/// The input bucket is prefixed with its length(u64, big endian) before hashing.
+pub struct FsCheckpoint {
+    dir: PathBuf,
+}
+
+impl FsCheckpoint {
+    fn bkts2path(&self, ibkt: &[u8], obkt: &[u8]) -> PathBuf {
+        let mut h = Sha256::new();
+        h.update((ibkt.len() as u64).to_be_bytes());
+        h.update(ibkt);
+        h.update(obkt);
+        let name: String = format!("{}.ckpt", hex::encode(&h.finalize()));
+        self.dir.join(name)
+    }
+}
+
+#[tonic::async_trait]
+impl Checkpoint for FsCheckpoint {
+    async fn last_key(&self, ibkt: &[u8], obkt: &[u8]) -> Result<Option<Vec<u8>>, Status> {
+        let p: PathBuf = self.bkts2path(ibkt, obkt);
+        match tokio::fs::read(p).await {
+            Ok(key) => Ok(Some(key)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Status::internal(format!(
+                "unable to read a checkpoint: {e}"
+            ))),
+        }
+    }
+
+    /// Saves the key to a temporary file and renames it(the old key is kept on failure)
+    async fn save(&self, ibkt: &[u8], obkt: &[u8], key: &[u8]) -> Result<(), Status> {
+        let p: PathBuf = self.bkts2path(ibkt, obkt);
+        let tmp: PathBuf = p.with_extension("ckpt.tmp");
+        tokio::fs::write(&tmp, key)
+            .await
+            .map_err(|e| Status::internal(format!("unable to write a checkpoint: {e}")))?;
+        tokio::fs::rename(&tmp, &p)
+            .await
+            .map_err(|e| Status::internal(format!("unable to save a checkpoint: {e}")))
+    }
+
+    async fn clear(&self, ibkt: &[u8], obkt: &[u8]) -> Result<(), Status> {
+        let p: PathBuf = self.bkts2path(ibkt, obkt);
+        match tokio::fs::remove_file(p).await {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(Status::internal(format!(
+                "unable to remove a checkpoint: {e}"
+            ))),
+        }
+    }
+}
+
+/// Creates a [`Checkpoint`] which stores checkpoints in the dir(the dir will be created)
+pub async fn fs_checkpoint_new<P>(dir: P) -> Result<impl Checkpoint, Status>
+where
+    P: Into<PathBuf>,
+{
+    let dir: PathBuf = dir.into();
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| Status::internal(format!("unable to create a checkpoint dir: {e}")))?;
+    Ok(FsCheckpoint { dir })
+}
+
+#[cfg(test)]
+mod test_fs {
+    mod fs_checkpoint_new {
+        use crate::conv::checkpoint::fs::fs_checkpoint_new;
+        use crate::conv::checkpoint::Checkpoint;
+        use crate::testing::TempDir;
+
+        #[tokio::test]
+        async fn save_and_clear() {
+            let dir = TempDir::new("checkpoint");
+            let cp = fs_checkpoint_new(dir.path()).await.unwrap();
+
+            let none: Option<Vec<u8>> = cp.last_key(b"in", b"out").await.unwrap();
+            assert_eq!(none, None);
+
+            cp.save(b"in", b"out", b"k1").await.unwrap();
+            cp.save(b"in", b"out", b"k2").await.unwrap();
+            cp.save(b"in", b"other", b"k3").await.unwrap();
+            let last: Option<Vec<u8>> = cp.last_key(b"in", b"out").await.unwrap();
+            assert_eq!(last, Some(b"k2".to_vec()));
+
+            cp.clear(b"in", b"out").await.unwrap();
+            let cleared: Option<Vec<u8>> = cp.last_key(b"in", b"out").await.unwrap();
+            assert_eq!(cleared, None);
+            let other: Option<Vec<u8>> = cp.last_key(b"in", b"other").await.unwrap();
+            assert_eq!(other, Some(b"k3".to_vec()));
+        }
+
+        #[tokio::test]
+        async fn long_buckets() {
+            let dir = TempDir::new("checkpoint-long");
+            let cp = fs_checkpoint_new(dir.path()).await.unwrap();
+
+            let ibkt: Vec<u8> = vec![b'i'; 1000];
+            let obkt: Vec<u8> = vec![b'o'; 1000];
+            cp.save(&ibkt, &obkt, b"k1").await.unwrap();
+            let last: Option<Vec<u8>> = cp.last_key(&ibkt, &obkt).await.unwrap();
+            assert_eq!(last, Some(b"k1".to_vec()));
+
+            cp.save(b"ab", b"c", b"k2").await.unwrap();
+            cp.save(b"a", b"bc", b"k3").await.unwrap();
+            let ab: Option<Vec<u8>> = cp.last_key(b"ab", b"c").await.unwrap();
+            assert_eq!(ab, Some(b"k2".to_vec()));
+        }
+    }
+}
@@ -0,0 +1,339 @@
+//! Reconciles keys of a source bucket and a target bucket
+//!
+//! Both buckets are sorted by key(spilled to temporary files if required)
+//! and merge-joined; missing, extra and mismatched keys are written as a JSON report.
+
+use futures::Stream;
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use tonic::Status;
+
+use crate::conv::verify::Mismatch;
+use crate::hex;
+
+pub mod spill;
+
+use spill::{Sorted, SpillConfig};
+
+/// Counts of a reconciliation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Reconciled {
+    /// Number of keys in the source
+    pub source_count: u64,
+
+    /// Number of keys in the target
+    pub target_count: u64,
+
+    /// Number of keys found in both buckets with the same check
+    pub matched: u64,
+
+    /// Number of keys found in the source but not in the target
+    pub missing_in_target: u64,
+
+    /// Number of keys found in the target but not in the source
+    pub extra_in_target: u64,
+
+    /// Number of keys found in both buckets with different checks
+    pub check_mismatched: u64,
+
+    /// Number of pairs which could not be compared(e.g, rejected pairs)
+    pub rejected: u64,
+}
+
+impl Reconciled {
+    /// Checks if all keys are reconciled
+    pub fn verified(&self) -> bool {
+        0 == self.missing_in_target + self.extra_in_target + self.check_mismatched + self.rejected
+    }
+
+    fn count(&mut self, m: &Mismatch) -> &'static str {
+        match (&m.source, &m.target) {
+            (Some(_), None) => {
+                self.missing_in_target += 1;
+                "missing_in_target"
+            }
+            (None, Some(_)) => {
+                self.extra_in_target += 1;
+                "extra_in_target"
+            }
+            _ => {
+                self.check_mismatched += 1;
+                "check_mismatched"
+            }
+        }
+    }
+}
+
+fn status(e: std::io::Error) -> Status {
+    Status::internal(format!("unable to write a report: {e}"))
+}
+
+/// Writes a JSON report.
+///
+/// ```text
+/// {"entries":[
+/// {"kind":"missing_in_target","key":"6b31","source":"6331","target":null}
+/// ],
+/// "summary":{"source_count":1,"target_count":0,...}}
+/// ```
+struct Report<'a, W> {
+    out: &'a mut W,
+    summary: Reconciled,
+}
+
+impl<'a, W> Report<'a, W>
+where
+    W: AsyncWrite + Unpin,
+{
+    async fn begin(out: &'a mut W) -> Result<Report<'a, W>, Status> {
+        out.write_all(br#"{"entries":["#).await.map_err(status)?;
+        Ok(Self {
+            out,
+            summary: Reconciled::default(),
+        })
+    }
+
+    async fn entry(&mut self, m: Mismatch) -> Result<(), Status> {
+        let first: bool = 0
+            == self.summary.missing_in_target
+                + self.summary.extra_in_target
+                + self.summary.check_mismatched;
+        let kind: &str = self.summary.count(&m);
+        let e = serde_json::json!({
+            "kind": kind,
+            "key": hex::encode(&m.key),
+            "source": m.source.as_deref().map(hex::encode),
+            "target": m.target.as_deref().map(hex::encode),
+        });
+        let sep: &[u8] = if first { b"\n" } else { b",\n" };
+        self.out.write_all(sep).await.map_err(status)?;
+        self.out
+            .write_all(e.to_string().as_bytes())
+            .await
+            .map_err(status)
+    }
+
+    async fn end(self, rejected: u64) -> Result<Reconciled, Status> {
+        let s = Reconciled {
+            rejected,
+            ..self.summary
+        };
+        let summary = serde_json::json!({
+            "source_count": s.source_count,
+            "target_count": s.target_count,
+            "matched": s.matched,
+            "missing_in_target": s.missing_in_target,
+            "extra_in_target": s.extra_in_target,
+            "check_mismatched": s.check_mismatched,
+            "rejected": s.rejected,
+        });
+        let tail: String = format!("\n],\n\"summary\":{summary}}}\n");
+        self.out.write_all(tail.as_bytes()).await.map_err(status)?;
+        self.out.flush().await.map_err(status)?;
+        Ok(s)
+    }
+}
+
+/// Merge-joins sorted source pairs and sorted target pairs(see [`reconcile`])
+pub async fn reconcile_sorted<W>(
+    mut source: Sorted,
+    mut target: Sorted,
+    rejected: u64,
+    keys_only: bool,
+    out: &mut W,
+) -> Result<Reconciled, Status>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut report = Report::begin(out).await?;
+    let mut s = source.next().await?;
+    let mut t = target.next().await?;
+    loop {
+        let ord = match (&s, &t) {
+            (None, None) => break,
+            (Some(_), None) => core::cmp::Ordering::Less,
+            (None, Some(_)) => core::cmp::Ordering::Greater,
+            (Some((sk, _)), Some((tk, _))) => sk.cmp(tk),
+        };
+        match ord {
+            core::cmp::Ordering::Less => {
+                let (key, check) = s.take().unwrap_or_default();
+                report.summary.source_count += 1;
+                report
+                    .entry(Mismatch {
+                        key,
+                        source: Some(check),
+                        target: None,
+                    })
+                    .await?;
+                s = source.next().await?;
+            }
+            core::cmp::Ordering::Greater => {
+                let (key, check) = t.take().unwrap_or_default();
+                report.summary.target_count += 1;
+                report
+                    .entry(Mismatch {
+                        key,
+                        source: None,
+                        target: Some(check),
+                    })
+                    .await?;
+                t = target.next().await?;
+            }
+            core::cmp::Ordering::Equal => {
+                let (key, scheck) = s.take().unwrap_or_default();
+                let (_, tcheck) = t.take().unwrap_or_default();
+                report.summary.source_count += 1;
+                report.summary.target_count += 1;
+                match scheck == tcheck || (keys_only && scheck.is_empty()) {
+                    true => report.summary.matched += 1,
+                    false => {
+                        report
+                            .entry(Mismatch {
+                                key,
+                                source: Some(scheck),
+                                target: Some(tcheck),
+                            })
+                            .await?
+                    }
+                }
+                s = source.next().await?;
+                t = target.next().await?;
+            }
+        }
+    }
+    report.end(rejected).await
+}
+
+/// Reconciles key/check pairs of a source with key/check pairs of a target.
+///
+/// Keys are expected to be unique in each bucket.
+///
+/// ## Arguments
+/// - source: key/check pairs computed from a source bucket(any order)
+/// - target: key/check pairs returned by a target bucket(any order)
+/// - rejected: Number of pairs which could not be compared
+/// - keys_only: An empty source check(e.g, [`NoCheck`](crate::checksum::NoCheck)) matches any
+///   target check if true(only keys are compared)
+/// - cfg: Where and when to spill pairs which do not fit in memory
+/// - out: The destination of the JSON report
+pub async fn reconcile<S, T, W>(
+    source: S,
+    target: T,
+    rejected: u64,
+    keys_only: bool,
+    cfg: &SpillConfig,
+    out: &mut W,
+) -> Result<Reconciled, Status>
+where
+    S: Stream<Item = spill::Pair>,
+    T: Stream<Item = spill::Pair>,
+    W: AsyncWrite + Unpin,
+{
+    let t: Sorted = spill::sort(target, cfg).await?;
+    let s: Sorted = spill::sort(source, cfg).await?;
+    reconcile_sorted(s, t, rejected, keys_only, out).await
+}
+
+#[cfg(test)]
+mod test_reconcile {
+    mod reconcile {
+        use crate::conv::reconcile::spill::SpillConfig;
+        use crate::conv::reconcile::{reconcile, Reconciled};
+        use crate::testing::TempDir;
+
+        fn pair(k: &[u8], c: &[u8]) -> (Vec<u8>, Vec<u8>) {
+            (k.to_vec(), c.to_vec())
+        }
+
+        async fn run(mem_limit: usize) -> (Reconciled, serde_json::Value) {
+            let dir = TempDir::new(&format!("reconcile-{mem_limit}"));
+            let cfg = SpillConfig {
+                dir: dir.path().to_path_buf(),
+                mem_limit,
+                max_fan_in: 2,
+            };
+            let source = futures::stream::iter(vec![
+                pair(b"k3", b"c3"),
+                pair(b"k1", b"c1"),
+                pair(b"k2", b"c2"),
+                pair(b"k5", b"c5"),
+            ]);
+            let target = futures::stream::iter(vec![
+                pair(b"k2", b"XX"),
+                pair(b"k0", b"c0"),
+                pair(b"k1", b"c1"),
+                pair(b"k5", b"c5"),
+            ]);
+            let mut out: Vec<u8> = vec![];
+            let r: Reconciled = reconcile(source, target, 0, false, &cfg, &mut out)
+                .await
+                .unwrap();
+            assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+            (r, serde_json::from_slice(&out).unwrap())
+        }
+
+        async fn check(mem_limit: usize) {
+            let (r, report) = run(mem_limit).await;
+            assert_eq!(
+                r,
+                Reconciled {
+                    source_count: 4,
+                    target_count: 4,
+                    matched: 2,
+                    missing_in_target: 1,
+                    extra_in_target: 1,
+                    check_mismatched: 1,
+                    rejected: 0,
+                }
+            );
+            assert!(!r.verified());
+            let entries = report["entries"].as_array().unwrap();
+            assert_eq!(entries.len(), 3);
+            assert_eq!(entries[0]["kind"], "extra_in_target");
+            assert_eq!(entries[0]["key"], "6b30");
+            assert_eq!(entries[1]["kind"], "check_mismatched");
+            assert_eq!(entries[1]["target"], "5858");
+            assert_eq!(entries[2]["kind"], "missing_in_target");
+            assert!(entries[2]["target"].is_null());
+            assert_eq!(report["summary"]["matched"], 2);
+        }
+
+        #[tokio::test]
+        async fn in_memory() {
+            check(1 << 20).await;
+        }
+
+        #[tokio::test]
+        async fn spilled() {
+            check(1).await;
+        }
+
+        #[tokio::test]
+        async fn empty_check() {
+            let dir = TempDir::new("reconcile-empty-check");
+            let cfg = SpillConfig {
+                dir: dir.path().to_path_buf(),
+                mem_limit: 1 << 20,
+                max_fan_in: 2,
+            };
+            let source = || futures::stream::iter(vec![pair(b"k1", b"")]);
+            let target = || futures::stream::iter(vec![pair(b"k1", b"c1")]);
+
+            let mut out: Vec<u8> = vec![];
+            let r: Reconciled = reconcile(source(), target(), 0, false, &cfg, &mut out)
+                .await
+                .unwrap();
+            assert_eq!(r.check_mismatched, 1);
+            assert!(!r.verified());
+
+            let mut out: Vec<u8> = vec![];
+            let r: Reconciled = reconcile(source(), target(), 0, true, &cfg, &mut out)
+                .await
+                .unwrap();
+            assert_eq!(r.matched, 1);
+            assert!(r.verified());
+        }
+    }
+}
@@ -0,0 +1,305 @@
+//! Sorts key/check pairs using temporary files if the pairs do not fit in memory
+
+use core::cmp::Reverse;
+
+use std::collections::BinaryHeap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use futures::Stream;
+use futures::StreamExt;
+
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
+
+use tonic::Status;
+
+/// A key and its check
+pub type Pair = (Vec<u8>, Vec<u8>);
+
+static RUN_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Where and when to spill pairs
+#[derive(Debug, Clone)]
+pub struct SpillConfig {
+    /// A directory to save temporary files
+    pub dir: PathBuf,
+
+    /// Pairs are saved to a temporary file if pairs in memory exceed this size(in bytes)
+    pub mem_limit: usize,
+
+    /// Max number of temporary files opened at once(at least 2); more files are merged in passes
+    pub max_fan_in: usize,
+}
+
+impl Default for SpillConfig {
+    fn default() -> Self {
+        Self {
+            dir: std::env::temp_dir(),
+            mem_limit: 64 * 1024 * 1024,
+            max_fan_in: 64,
+        }
+    }
+}
+
+fn pair_size(p: &Pair) -> usize {
+    let (key, check) = p;
+    key.len() + check.len() + 2 * core::mem::size_of::<Vec<u8>>()
+}
+
+fn status(e: io::Error) -> Status {
+    Status::internal(format!("unable to spill pairs: {e}"))
+}
+
+fn len32(b: &[u8]) -> Result<u32, Status> {
+    u32::try_from(b.len()).map_err(|_| {
+        Status::resource_exhausted(format!("unable to spill {} bytes of a key/check", b.len()))
+    })
+}
+
+/// A sorted temporary file(removed when read to the end or dropped)
+struct Run {
+    path: Option<PathBuf>,
+}
+
+impl Run {
+    async fn remove(&mut self) -> Result<(), Status> {
+        match self.path.take() {
+            None => Ok(()),
+            Some(p) => tokio::fs::remove_file(p).await.map_err(status),
+        }
+    }
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        let p: PathBuf = match self.path.take() {
+            None => return,
+            Some(p) => p,
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(h) => drop(h.spawn_blocking(move || std::fs::remove_file(p))),
+            Err(_) => {
+                let _ = std::fs::remove_file(p);
+            }
+        }
+    }
+}
+
+struct RunWriter {
+    run: Run,
+    w: BufWriter<File>,
+}
+
+impl RunWriter {
+    async fn create(dir: &Path) -> Result<Self, Status> {
+        let seq: u64 = RUN_SEQ.fetch_add(1, Ordering::Relaxed);
+        let path: PathBuf = dir.join(format!("fs2db-spill-{}-{seq}.run", std::process::id()));
+        let f: File = File::create(&path).await.map_err(status)?;
+        Ok(Self {
+            run: Run { path: Some(path) },
+            w: BufWriter::new(f),
+        })
+    }
+
+    async fn write(&mut self, p: &Pair) -> Result<(), Status> {
+        let (key, check) = p;
+        self.w.write_u32(len32(key)?).await.map_err(status)?;
+        self.w.write_all(key).await.map_err(status)?;
+        self.w.write_u32(len32(check)?).await.map_err(status)?;
+        self.w.write_all(check).await.map_err(status)
+    }
+
+    /// Flushes and closes the file
+    async fn finish(mut self) -> Result<Run, Status> {
+        self.w.flush().await.map_err(status)?;
+        Ok(self.run)
+    }
+
+    async fn sorted(dir: &Path, sorted: Vec<Pair>) -> Result<Run, Status> {
+        let mut w = Self::create(dir).await?;
+        for p in &sorted {
+            w.write(p).await?;
+        }
+        w.finish().await
+    }
+}
+
+struct RunReader {
+    rdr: BufReader<File>,
+    run: Run,
+}
+
+impl RunReader {
+    async fn open(run: Run) -> Result<Self, Status> {
+        let path: &Path = run
+            .path
+            .as_deref()
+            .ok_or_else(|| Status::internal("spilled pairs already removed"))?;
+        let f: File = File::open(path).await.map_err(status)?;
+        Ok(Self {
+            rdr: BufReader::new(f),
+            run,
+        })
+    }
+
+    async fn read_bytes(&mut self, len: u32) -> Result<Vec<u8>, Status> {
+        let mut buf: Vec<u8> = vec![0; len as usize];
+        self.rdr.read_exact(&mut buf).await.map_err(status)?;
+        Ok(buf)
+    }
+
+    async fn next(&mut self) -> Result<Option<Pair>, Status> {
+        let klen: u32 = match self.rdr.read_u32().await {
+            Ok(l) => l,
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                self.run.remove().await?;
+                return Ok(None);
+            }
+            Err(e) => return Err(status(e)),
+        };
+        let key: Vec<u8> = self.read_bytes(klen).await?;
+        let clen: u32 = self.rdr.read_u32().await.map_err(status)?;
+        let check: Vec<u8> = self.read_bytes(clen).await?;
+        Ok(Some((key, check)))
+    }
+}
+
+/// Merges sorted runs
+struct Merger {
+    readers: Vec<RunReader>,
+    heap: BinaryHeap<Reverse<(Pair, usize)>>,
+}
+
+impl Merger {
+    async fn open(runs: Vec<Run>) -> Result<Self, Status> {
+        let mut readers: Vec<RunReader> = Vec::with_capacity(runs.len());
+        let mut heap = BinaryHeap::with_capacity(runs.len());
+        for run in runs {
+            let mut rdr = RunReader::open(run).await?;
+            if let Some(first) = rdr.next().await? {
+                heap.push(Reverse((first, readers.len())));
+            }
+            readers.push(rdr);
+        }
+        Ok(Self { readers, heap })
+    }
+
+    async fn next(&mut self) -> Result<Option<Pair>, Status> {
+        let (pair, ix) = match self.heap.pop() {
+            None => return Ok(None),
+            Some(Reverse(item)) => item,
+        };
+        if let Some(nex) = self.readers[ix].next().await? {
+            self.heap.push(Reverse((nex, ix)));
+        }
+        Ok(Some(pair))
+    }
+
+    /// Merges runs into a run
+    async fn merge(dir: &Path, runs: Vec<Run>) -> Result<Run, Status> {
+        let mut m = Self::open(runs).await?;
+        let mut w = RunWriter::create(dir).await?;
+        while let Some(p) = m.next().await? {
+            w.write(&p).await?;
+        }
+        w.finish().await
+    }
+}
+
+enum Merged {
+    Mem(std::vec::IntoIter<Pair>),
+    Runs(Merger),
+}
+
+/// Pairs sorted by key
+pub struct Sorted {
+    merged: Merged,
+}
+
+impl Sorted {
+    /// Gets the pair with the smallest key
+    pub async fn next(&mut self) -> Result<Option<Pair>, Status> {
+        match &mut self.merged {
+            Merged::Mem(i) => Ok(i.next()),
+            Merged::Runs(m) => m.next().await,
+        }
+    }
+}
+
+/// Sorts pairs by key(pairs are saved to temporary files if pairs exceed the limit).
+///
+/// Temporary files are closed until merged; at most [`SpillConfig::max_fan_in`] files are
+/// opened at once.
+pub async fn sort<S>(pairs: S, cfg: &SpillConfig) -> Result<Sorted, Status>
+where
+    S: Stream<Item = Pair>,
+{
+    let mut pairs = Box::pin(pairs);
+    let mut buf: Vec<Pair> = vec![];
+    let mut size: usize = 0;
+    let mut runs: Vec<Run> = vec![];
+    while let Some(p) = pairs.next().await {
+        size += pair_size(&p);
+        buf.push(p);
+        if cfg.mem_limit < size {
+            buf.sort();
+            runs.push(RunWriter::sorted(&cfg.dir, core::mem::take(&mut buf)).await?);
+            size = 0;
+        }
+    }
+    buf.sort();
+    if runs.is_empty() {
+        let merged = Merged::Mem(buf.into_iter());
+        return Ok(Sorted { merged });
+    }
+    if !buf.is_empty() {
+        runs.push(RunWriter::sorted(&cfg.dir, buf).await?);
+    }
+    let fan_in: usize = cfg.max_fan_in.max(2);
+    while fan_in < runs.len() {
+        let mut merged: Vec<Run> = Vec::with_capacity(runs.len().div_ceil(fan_in));
+        let mut rest = runs.into_iter();
+        loop {
+            let group: Vec<Run> = rest.by_ref().take(fan_in).collect();
+            if group.is_empty() {
+                break;
+            }
+            merged.push(Merger::merge(&cfg.dir, group).await?);
+        }
+        runs = merged;
+    }
+    let merged = Merged::Runs(Merger::open(runs).await?);
+    Ok(Sorted { merged })
+}
+
+#[cfg(test)]
+mod test_spill {
+    mod sort {
+        use crate::conv::reconcile::spill::{sort, Pair, Sorted, SpillConfig};
+        use crate::testing::TempDir;
+
+        #[tokio::test]
+        async fn passes() {
+            let dir = TempDir::new("spill");
+            let cfg = SpillConfig {
+                dir: dir.path().to_path_buf(),
+                mem_limit: 0,
+                max_fan_in: 3,
+            };
+            let pairs: Vec<Pair> = (0..20u8).rev().map(|k| (vec![k], vec![k, k])).collect();
+            let mut sorted: Sorted = sort(futures::stream::iter(pairs), &cfg).await.unwrap();
+            let cnt: usize = std::fs::read_dir(dir.path()).unwrap().count();
+            assert!(cnt <= 3, "{cnt} files opened at once");
+
+            let mut got: Vec<Pair> = vec![];
+            while let Some(p) = sorted.next().await.unwrap() {
+                got.push(p);
+            }
+            let expected: Vec<Pair> = (0..20u8).map(|k| (vec![k], vec![k, k])).collect();
+            assert_eq!(got, expected);
+            assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+        }
+    }
+}
@@ -1,2 +1,5 @@
 #[cfg(all(feature = "source", feature = "target"))]
 pub mod src2tgt;
+
+#[cfg(all(feature = "source", feature = "target"))]
+pub mod parallel;
@@ -0,0 +1,163 @@
+//! Migrates many buckets concurrently
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::stream::{Stream, StreamExt};
+
+use tonic::{transport::Channel, Status};
+
+use crate::checksum::Checksum;
+use crate::conv::rpc::src2tgt::{upsert_selected, UpsertReport};
+use crate::output::dead::DeadLetter;
+
+use crate::rpc::fs2db::proto::source;
+use source::v1::select_service_client::SelectServiceClient as SelSrc;
+use source::v1::InputBucket;
+
+use crate::rpc::fs2db::proto::target;
+use target::v1::upsert_service_client::UpsertServiceClient;
+use target::v1::OutputBucket;
+
+/// The outcome of a bucket migration
+#[derive(Debug)]
+pub struct BucketOutcome {
+    pub input: InputBucket,
+    pub output: OutputBucket,
+
+    /// Number of rows upserted/rejected or the error which stopped the migration
+    pub report: Result<UpsertReport, Status>,
+
+    /// Time taken to migrate the bucket
+    pub duration: Duration,
+}
+
+impl BucketOutcome {
+    /// Number of rows upserted(0 if failed)
+    pub fn upserted(&self) -> u64 {
+        self.report.as_ref().map(|r| r.upserted).unwrap_or_default()
+    }
+}
+
+/// Migrates buckets using [`upsert_selected`] concurrently.
+///
+/// A failed bucket does not stop other buckets;
+/// the error is saved to its [`BucketOutcome`].
+///
+/// ## Arguments
+/// - u: The upsert client(cloned for each bucket)
+/// - s: The source select client(cloned for each bucket)
+/// - buckets: Input/output bucket pairs
+/// - dl: A [`DeadLetter`] shared by all buckets
+/// - cks: A [`Checksum`] shared by all buckets
+/// - concurrency: Max number of buckets migrated at the same time(at least 1)
+///
+/// ## Returns
+/// Outcomes in completion order(not in the order of the buckets)
+pub async fn upsert_buckets<B, C, D>(
+    u: UpsertServiceClient<Channel>,
+    s: SelSrc<Channel>,
+    buckets: B,
+    dl: Arc<D>,
+    cks: Arc<C>,
+    concurrency: usize,
+) -> Vec<BucketOutcome>
+where
+    B: Stream<Item = (InputBucket, OutputBucket)>,
+    C: Checksum,
+    D: DeadLetter,
+{
+    buckets
+        .map(|pair| {
+            let (input, output) = pair;
+            let mut u = u.clone();
+            let mut s = s.clone();
+            let dl = dl.clone();
+            let cks = cks.clone();
+            async move {
+                let started = Instant::now();
+                let report: Result<UpsertReport, Status> =
+                    upsert_selected(&mut u, &mut s, input.clone(), output.clone(), dl, cks).await;
+                BucketOutcome {
+                    input,
+                    output,
+                    report,
+                    duration: started.elapsed(),
+                }
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await
+}
+
+#[cfg(test)]
+mod test_parallel {
+    mod upsert_buckets {
+        use std::sync::atomic::Ordering;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        use tonic::transport::{Channel, Server};
+        use tonic::{Code, Status};
+
+        use crate::checksum::NoCheck;
+        use crate::conv::rpc::parallel::{upsert_buckets, BucketOutcome};
+        use crate::testing::rpc::{pair, serve, MemSource, MemTarget};
+        use crate::testing::MemDeadLetter;
+
+        use crate::rpc::fs2db::proto::source;
+        use source::v1::select_service_client::SelectServiceClient as SelSrc;
+        use source::v1::select_service_server::SelectServiceServer;
+        use source::v1::InputBucket;
+
+        use crate::rpc::fs2db::proto::target;
+        use target::v1::upsert_service_client::UpsertServiceClient;
+        use target::v1::upsert_service_server::UpsertServiceServer;
+        use target::v1::OutputBucket;
+
+        #[tokio::test]
+        async fn limited() {
+            let src = Arc::new(MemSource {
+                pause: Some((0, Duration::from_millis(50))),
+                ..MemSource::new(vec![pair(b"k1", b"v1"), pair(b"k2", b"v2")])
+            });
+            src.fails.lock().unwrap().extend(vec![
+                None,
+                Some((1, Status::unavailable("source lost"))),
+                None,
+                None,
+            ]);
+            let ch: Channel = serve(
+                Server::builder()
+                    .add_service(SelectServiceServer::from_arc(src.clone()))
+                    .add_service(UpsertServiceServer::new(MemTarget::default())),
+            )
+            .await;
+            let buckets = (0..4u8).map(|b| {
+                let i = InputBucket { bucket: vec![b] };
+                let o = OutputBucket { bucket: vec![b] };
+                (i, o)
+            });
+            let outcomes: Vec<BucketOutcome> = upsert_buckets(
+                UpsertServiceClient::new(ch.clone()),
+                SelSrc::new(ch),
+                futures::stream::iter(buckets),
+                Arc::new(MemDeadLetter::default()),
+                Arc::new(NoCheck {}),
+                2,
+            )
+            .await;
+            assert_eq!(outcomes.len(), 4);
+            assert_eq!(src.max_active.load(Ordering::SeqCst), 2);
+
+            let (failed, ok): (Vec<BucketOutcome>, Vec<BucketOutcome>) =
+                outcomes.into_iter().partition(|o| o.report.is_err());
+            assert_eq!(failed.len(), 1);
+            assert_eq!(failed[0].upserted(), 0);
+            let e: &Status = failed[0].report.as_ref().unwrap_err();
+            assert_eq!(e.code(), Code::Unavailable);
+            assert!(ok.iter().all(|o| 2 == o.upserted()));
+        }
+    }
+}
@@ -1,62 +1,355 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
 use futures::stream::StreamExt;
 
-use tonic::{transport::Channel, Status};
+use tonic::{transport::Channel, Code, Status};
+
+use crate::checksum::Checksum;
+use crate::conv::checkpoint::Checkpoint;
+#[cfg(all(feature = "json", feature = "async_tokio"))]
+use crate::conv::reconcile::spill::{self, Sorted, SpillConfig};
+#[cfg(all(feature = "json", feature = "async_tokio"))]
+use crate::conv::reconcile::{reconcile_sorted, Reconciled};
+use crate::conv::verify::{compare_checks, Compared, DiffSummary, Mismatch};
+use crate::item::item_status;
+use crate::output::dead::{reject_errs, rejected_count, DeadLetter};
+#[cfg(feature = "async_tokio")]
+use crate::retry::{retry, RetryPolicy};
 
 use crate::rpc::fs2db::proto::source;
 use source::v1::drop_service_client::DropServiceClient;
 use source::v1::drop_svc::CheckedRequest;
 use source::v1::select_service_client::SelectServiceClient as SelSrc;
 use source::v1::AllRequest as SelAll;
-use source::v1::InputBucket;
+use source::v1::{AllResponse, InputBucket};
 
 use crate::rpc::fs2db::proto::target;
 use target::v1::sel_svc::AllRequest as TgtAll;
 use target::v1::select_service_client::SelectServiceClient as SelTgt;
+use target::v1::upsert_batch_service_client::UpsertBatchServiceClient;
 use target::v1::upsert_service_client::UpsertServiceClient;
-use target::v1::upst_svc::ManyRequest;
+use target::v1::upst_svc::{BatchRequest, ManyRequest, Pair};
 use target::v1::OutputBucket;
 
+/// Number of rows upserted/rejected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UpsertReport {
+    /// Number of rows inserted or updated
+    pub upserted: u64,
+
+    /// Number of rows sent to the [`DeadLetter`]
+    pub rejected: u64,
+}
+
+/// Number of keys dropped/rejected
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DropReport {
+    /// Number of keys which was stored in a dropped bucket
+    pub keys_count: u64,
+
+    /// Number of keys sent to the [`DeadLetter`]
+    pub rejected: u64,
+}
+
+/// Converts a pair rejected in-band by the source to an item error(see [`reject_errs`]).
+fn inband(a: AllResponse) -> Result<AllResponse, Status> {
+    match a.rejected {
+        None => Ok(a),
+        Some(r) => Err(item_status(Code::from(r.code), r.message, &a.key, a.val)),
+    }
+}
+
+fn pair2req<C>(o: &OutputBucket, a: AllResponse, cks: &C) -> ManyRequest
+where
+    C: Checksum,
+{
+    let check: Vec<u8> = cks.check(&a.val);
+    ManyRequest {
+        bkt: Some(o.clone()),
+        key: a.key,
+        val: a.val,
+        check,
+    }
+}
+
 /// Gets all key/val pairs from a bucket and upserts all of them.
 /// 1. Gets all key/val pairs from a bucket
-/// 2. Ignore errors(incomplete key/val pairs will be rejected later)
-/// 3. Upserts all
-/// 4. Returns number of rows upserted
-pub async fn upsert_selected(
+/// 2. Sends pairs rejected by the source to the [`DeadLetter`]
+/// 3. Upserts all with checks computed by the [`Checksum`]
+/// 4. Returns number of rows upserted/rejected
+///
+/// Fails if the select stream ends with an error(e.g, the connection was lost mid-bucket);
+/// rows upserted before the error are kept.
+pub async fn upsert_selected<C, D>(
     u: &mut UpsertServiceClient<Channel>,
     s: &mut SelSrc<Channel>,
     i: InputBucket,
     o: OutputBucket,
-) -> Result<u64, Status> {
-    let req = SelAll { bkt: Some(i) };
-    let pairs = s.all(req).await?.into_inner();
-    let noerr = pairs.filter_map(|r| async move { r.ok() });
-    let reqs = noerr.map(move |a| {
-        let key = a.key;
-        let val = a.val;
-        ManyRequest {
+    dl: Arc<D>,
+    cks: Arc<C>,
+) -> Result<UpsertReport, Status>
+where
+    C: Checksum,
+    D: DeadLetter,
+{
+    let ibkt: Vec<u8> = i.bucket.clone();
+    let req = SelAll {
+        bkt: Some(i),
+        start_after: vec![],
+    };
+    let pairs = s.all(req).await?.into_inner().map(|r| r.map(inband));
+    let (noerr, rejecting) = reject_errs(pairs, ibkt, dl);
+    let reqs = noerr.map(move |a| pair2req(&o, a, cks.as_ref()));
+    let res = u.many(reqs).await?.into_inner();
+    let rejected: u64 = rejected_count(rejecting).await?;
+    Ok(UpsertReport {
+        upserted: res.upserted,
+        rejected,
+    })
+}
+
+/// Upserts a bucket using [`upsert_selected`] and retries the whole bucket on transient errors.
+///
+/// Errors of the select stream(e.g, `Unavailable` during a long transfer) are retried as well.
+/// Upserts are idempotent; rows upserted by a failed attempt are upserted again.
+/// Rejected rows of a failed attempt may be sent to the [`DeadLetter`] more than once.
+///
+/// ## Arguments
+/// - policy: The [`RetryPolicy`] which decides whether and when to retry
+#[cfg(feature = "async_tokio")]
+#[allow(clippy::too_many_arguments)]
+pub async fn upsert_selected_retried<C, D>(
+    u: &mut UpsertServiceClient<Channel>,
+    s: &mut SelSrc<Channel>,
+    i: InputBucket,
+    o: OutputBucket,
+    dl: Arc<D>,
+    cks: Arc<C>,
+    policy: &RetryPolicy,
+) -> Result<UpsertReport, Status>
+where
+    C: Checksum,
+    D: DeadLetter,
+{
+    retry(policy, || {
+        let mut u = u.clone();
+        let mut s = s.clone();
+        let (i, o) = (i.clone(), o.clone());
+        let (dl, cks) = (dl.clone(), cks.clone());
+        async move { upsert_selected(&mut u, &mut s, i, o, dl, cks).await }
+    })
+    .await
+}
+
+/// Batch options of [`upsert_selected_batched`]
+#[derive(Debug, Clone, Copy)]
+pub struct BatchConfig {
+    /// Max number of rows in a batch
+    pub size: usize,
+
+    /// A batch is sent after this interval even if the batch is not full
+    pub interval: Duration,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            size: 1024,
+            interval: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Gets all key/val pairs from a bucket and upserts all of them in batches.
+/// 1. Gets all key/val pairs from a bucket
+/// 2. Sends pairs rejected by the source to the [`DeadLetter`]
+/// 3. Groups key/val pairs into batches(see [`BatchConfig`])
+/// 4. Upserts all batches with checks computed by the [`Checksum`]
+/// 5. Returns number of rows upserted/rejected
+pub async fn upsert_selected_batched<C, D>(
+    u: &mut UpsertBatchServiceClient<Channel>,
+    s: &mut SelSrc<Channel>,
+    i: InputBucket,
+    o: OutputBucket,
+    dl: Arc<D>,
+    cks: Arc<C>,
+    cfg: BatchConfig,
+) -> Result<UpsertReport, Status>
+where
+    C: Checksum,
+    D: DeadLetter,
+{
+    let ibkt: Vec<u8> = i.bucket.clone();
+    let req = SelAll {
+        bkt: Some(i),
+        start_after: vec![],
+    };
+    let pairs = s.all(req).await?.into_inner().map(|r| r.map(inband));
+    let (noerr, rejecting) = reject_errs(pairs, ibkt, dl);
+    let batches = tokio_stream::StreamExt::chunks_timeout(noerr, cfg.size.max(1), cfg.interval);
+    let reqs = batches.map(move |batch| {
+        let pairs: Vec<Pair> = batch
+            .into_iter()
+            .map(|a| Pair {
+                check: cks.check(&a.val),
+                key: a.key,
+                val: a.val,
+            })
+            .collect();
+        BatchRequest {
             bkt: Some(o.clone()),
-            key,
-            val,
+            pairs,
         }
     });
     let res = u.many(reqs).await?.into_inner();
-    Ok(res.upserted)
+    let rejected: u64 = rejected_count(rejecting).await?;
+    Ok(UpsertReport {
+        upserted: res.upserted,
+        rejected,
+    })
+}
+
+/// Upserts a bucket using [`upsert_selected_batched`] and retries the whole bucket on transient errors.
+///
+/// See [`upsert_selected_retried`].
+#[cfg(feature = "async_tokio")]
+#[allow(clippy::too_many_arguments)]
+pub async fn upsert_selected_batched_retried<C, D>(
+    u: &mut UpsertBatchServiceClient<Channel>,
+    s: &mut SelSrc<Channel>,
+    i: InputBucket,
+    o: OutputBucket,
+    dl: Arc<D>,
+    cks: Arc<C>,
+    cfg: BatchConfig,
+    policy: &RetryPolicy,
+) -> Result<UpsertReport, Status>
+where
+    C: Checksum,
+    D: DeadLetter,
+{
+    retry(policy, || {
+        let mut u = u.clone();
+        let mut s = s.clone();
+        let (i, o) = (i.clone(), o.clone());
+        let (dl, cks) = (dl.clone(), cks.clone());
+        async move { upsert_selected_batched(&mut u, &mut s, i, o, dl, cks, cfg).await }
+    })
+    .await
+}
+
+/// Gets key/val pairs after the last checkpoint and upserts them chunk by chunk.
+/// 1. Gets the last acknowledged key of the bucket pair from the [`Checkpoint`]
+/// 2. Gets all key/val pairs after the key from a bucket(the source must return sorted keys)
+/// 3. Sends pairs rejected by the source to the [`DeadLetter`]
+/// 4. Upserts each chunk(up to chunk_size rows) with checks computed by the [`Checksum`]
+/// 5. Saves the last key of the chunk after the chunk is acknowledged
+/// 6. Returns number of rows upserted/rejected by this run
+///
+/// Fails if the select stream ends with an error(e.g, the source failed mid-bucket);
+/// the checkpoint keeps the last acknowledged key and the next run resumes after it.
+///
+/// The checkpoint is kept after completion; use [`Checkpoint::clear`] to migrate the bucket again.
+#[allow(clippy::too_many_arguments)]
+pub async fn upsert_selected_resumable<C, D, P>(
+    u: &mut UpsertServiceClient<Channel>,
+    s: &mut SelSrc<Channel>,
+    i: InputBucket,
+    o: OutputBucket,
+    dl: Arc<D>,
+    cks: Arc<C>,
+    cp: &P,
+    chunk_size: usize,
+) -> Result<UpsertReport, Status>
+where
+    C: Checksum,
+    D: DeadLetter,
+    P: Checkpoint,
+{
+    let start_after: Vec<u8> = cp.last_key(&i.bucket, &o.bucket).await?.unwrap_or_default();
+    let req = SelAll {
+        bkt: Some(i.clone()),
+        start_after,
+    };
+    let pairs = s.all(req).await?.into_inner().map(|r| r.map(inband));
+    let (noerr, rejecting) = reject_errs(pairs, i.bucket.clone(), dl);
+    let mut chunks = noerr.chunks(chunk_size.max(1));
+    let mut tot: u64 = 0;
+    while let Some(chunk) = chunks.next().await {
+        let last: Vec<u8> = match chunk.last() {
+            None => continue,
+            Some(a) => a.key.clone(),
+        };
+        let reqs: Vec<ManyRequest> = chunk
+            .into_iter()
+            .map(|a| pair2req(&o, a, cks.as_ref()))
+            .collect();
+        let res = u.many(futures::stream::iter(reqs)).await?.into_inner();
+        cp.save(&i.bucket, &o.bucket, &last).await?;
+        tot += res.upserted;
+    }
+    let rejected: u64 = rejected_count(rejecting).await?;
+    Ok(UpsertReport {
+        upserted: tot,
+        rejected,
+    })
+}
+
+/// Upserts a bucket using [`upsert_selected_resumable`] and retries on transient errors.
+///
+/// Each attempt resumes after the last checkpoint instead of upserting the whole bucket again.
+/// The report counts the rows of the last attempt only.
+#[cfg(feature = "async_tokio")]
+#[allow(clippy::too_many_arguments)]
+pub async fn upsert_selected_resumable_retried<C, D, P>(
+    u: &mut UpsertServiceClient<Channel>,
+    s: &mut SelSrc<Channel>,
+    i: InputBucket,
+    o: OutputBucket,
+    dl: Arc<D>,
+    cks: Arc<C>,
+    cp: &P,
+    chunk_size: usize,
+    policy: &RetryPolicy,
+) -> Result<UpsertReport, Status>
+where
+    C: Checksum,
+    D: DeadLetter,
+    P: Checkpoint,
+{
+    retry(policy, || {
+        let mut u = u.clone();
+        let mut s = s.clone();
+        let (i, o) = (i.clone(), o.clone());
+        let (dl, cks) = (dl.clone(), cks.clone());
+        async move {
+            upsert_selected_resumable(&mut u, &mut s, i, o, dl, cks, cp, chunk_size).await
+        }
+    })
+    .await
 }
 
 /// Drop a source bucket after verification.
 /// 1. Gets all key/check pairs from a target bucket
-/// 2. Ignore errors(incomplete pairs will be rejected later)
+/// 2. Fails if the stream of the target ends with an error
 /// 3. Drop a source bucket if all pairs verified
-/// 4. Returns number of keys which was stored in a source bucket
-pub async fn drop_all_if_verified(
+/// 4. Returns number of keys which was stored in a source bucket and number of rejected keys
+pub async fn drop_all_if_verified<D>(
     t: &mut SelTgt<Channel>,
     d: &mut DropServiceClient<Channel>,
     i: InputBucket,
     o: OutputBucket,
-) -> Result<u64, Status> {
+    dl: Arc<D>,
+) -> Result<DropReport, Status>
+where
+    D: DeadLetter,
+{
+    let obkt: Vec<u8> = o.bucket.clone();
     let req = TgtAll { bkt: Some(o) };
-    let pairs = t.all(req).await?.into_inner();
-    let noerr = pairs.filter_map(|r: Result<_, _>| async move { r.ok() });
+    let pairs = t.all(req).await?.into_inner().map(|r| r.map(Ok));
+    let (noerr, rejecting) = reject_errs(pairs, obkt, dl);
     let reqs = noerr.map(move |a| {
         let key = a.key;
         let check = a.check;
@@ -67,5 +360,675 @@ pub async fn drop_all_if_verified(
         }
     });
     let res = d.checked(reqs).await?.into_inner();
-    Ok(res.keys_count)
+    let rejected: u64 = rejected_count(rejecting).await?;
+    Ok(DropReport {
+        keys_count: res.keys_count,
+        rejected,
+    })
+}
+
+/// The result of [`drop_all_if_checks_match`]
+#[derive(Debug, Clone)]
+pub enum CheckedDrop {
+    /// The source bucket was dropped
+    Dropped(DropReport),
+
+    /// The source bucket was kept
+    Kept {
+        /// Missing keys and keys with different checks
+        mismatched: Vec<Mismatch>,
+
+        /// Keys found more than once in the source
+        duplicated: Vec<Vec<u8>>,
+
+        /// Number of pairs sent to the [`DeadLetter`]
+        rejected: u64,
+    },
+}
+
+/// Gets key/check pairs from a target bucket and builds a map.
+async fn target_checks<D>(
+    t: &mut SelTgt<Channel>,
+    o: OutputBucket,
+    dl: Arc<D>,
+) -> Result<(BTreeMap<Vec<u8>, Vec<u8>>, u64), Status>
+where
+    D: DeadLetter,
+{
+    let obkt: Vec<u8> = o.bucket.clone();
+    let req = TgtAll { bkt: Some(o) };
+    let pairs = t.all(req).await?.into_inner().map(|r| r.map(Ok));
+    let (noerr, rejecting) = reject_errs(pairs, obkt, dl);
+    let checks: BTreeMap<Vec<u8>, Vec<u8>> = noerr
+        .fold(BTreeMap::new(), |mut m, a| async move {
+            m.insert(a.key, a.check);
+            m
+        })
+        .await;
+    let rejected: u64 = rejected_count(rejecting).await?;
+    Ok((checks, rejected))
+}
+
+/// Compares key/check pairs of a target bucket with checks computed from a source bucket.
+///
+/// Returns the compared checks and number of rejected pairs.
+/// Empty checks match any check only if keys_only is true(see [`compare_checks`]).
+pub async fn compare_buckets<C, D>(
+    t: &mut SelTgt<Channel>,
+    s: &mut SelSrc<Channel>,
+    i: InputBucket,
+    o: OutputBucket,
+    dl: Arc<D>,
+    cks: &C,
+    keys_only: bool,
+) -> Result<(Compared, u64), Status>
+where
+    C: Checksum,
+    D: DeadLetter,
+{
+    let (checks, trejected) = target_checks(t, o, dl.clone()).await?;
+    let ibkt: Vec<u8> = i.bucket.clone();
+    let req = SelAll {
+        bkt: Some(i),
+        start_after: vec![],
+    };
+    let pairs = s.all(req).await?.into_inner().map(|r| r.map(inband));
+    let (noerr, rejecting) = reject_errs(pairs, ibkt, dl);
+    let computed = noerr.map(|a| {
+        let check: Vec<u8> = cks.check(&a.val);
+        (a.key, check)
+    });
+    let compared: Compared = compare_checks(computed, checks, keys_only).await;
+    let srejected: u64 = rejected_count(rejecting).await?;
+    Ok((compared, trejected + srejected))
+}
+
+/// Drop a source bucket if the checks of the source match the checks of the target.
+/// 1. Gets all key/check pairs from a target bucket
+/// 2. Gets all key/val pairs from a source bucket and computes checks using the [`Checksum`]
+/// 3. Sends pairs rejected by the source to the [`DeadLetter`]
+/// 4. Keeps the source bucket if any key is missing or duplicated, any check differs or any pair
+///    is rejected
+/// 5. Drop the source bucket otherwise
+///
+/// The target must return the checks sent by [`upsert_selected`](computed by the same [`Checksum`]).
+/// An empty source check matches an empty target check only; a bucket is never dropped on key
+/// matches alone if the target has checks.
+pub async fn drop_all_if_checks_match<C, D>(
+    t: &mut SelTgt<Channel>,
+    s: &mut SelSrc<Channel>,
+    d: &mut DropServiceClient<Channel>,
+    i: InputBucket,
+    o: OutputBucket,
+    dl: Arc<D>,
+    cks: &C,
+) -> Result<CheckedDrop, Status>
+where
+    C: Checksum,
+    D: DeadLetter,
+{
+    let (compared, rejected) = compare_buckets(t, s, i.clone(), o, dl, cks, false).await?;
+    let kept: bool = !compared.mismatched.is_empty() || !compared.duplicated.is_empty();
+    if kept || 0 < rejected {
+        return Ok(CheckedDrop::Kept {
+            mismatched: compared.mismatched,
+            duplicated: compared.duplicated,
+            rejected,
+        });
+    }
+    let reqs = compared.matched.into_iter().map(move |pair| {
+        let (key, check) = pair;
+        CheckedRequest {
+            bkt: Some(i.clone()),
+            key,
+            check,
+        }
+    });
+    let res = d.checked(futures::stream::iter(reqs)).await?.into_inner();
+    Ok(CheckedDrop::Dropped(DropReport {
+        keys_count: res.keys_count,
+        rejected,
+    }))
+}
+
+/// Compares a source bucket with a target bucket without dropping the source bucket.
+/// 1. Gets all key/check pairs from a target bucket
+/// 2. Gets all key/val pairs from a source bucket and computes checks using the [`Checksum`]
+/// 3. Sends pairs rejected by the source to the [`DeadLetter`]
+/// 4. Returns the summary of the differences(the drop RPC is never called)
+///
+/// Use [`DiffSummary::verified`] to check if [`drop_all_if_checks_match`] would drop the bucket
+/// (keys_only must be false to predict the drop; see [`compare_checks`]).
+pub async fn drop_dry_run<C, D>(
+    t: &mut SelTgt<Channel>,
+    s: &mut SelSrc<Channel>,
+    i: InputBucket,
+    o: OutputBucket,
+    dl: Arc<D>,
+    cks: &C,
+    keys_only: bool,
+) -> Result<DiffSummary, Status>
+where
+    C: Checksum,
+    D: DeadLetter,
+{
+    let (compared, rejected) = compare_buckets(t, s, i, o, dl, cks, keys_only).await?;
+    Ok(DiffSummary::new(compared, rejected))
+}
+
+/// Reconciles a source bucket with a target bucket and writes a JSON report.
+///
+/// 1. Gets all key/check pairs from the target and sorts them(spilled if required)
+/// 2. Gets all key/val pairs from the source, computes checks and sorts them
+/// 3. Merge-joins both and writes missing, extra and mismatched keys to the report
+///
+/// Unlike [`compare_buckets`], the keys need not fit in memory.
+///
+/// ## Arguments
+/// - t: The target select client
+/// - s: The source select client
+/// - i: The input bucket
+/// - o: The output bucket
+/// - dl: A [`DeadLetter`] for pairs which could not be compared
+/// - cks: A [`Checksum`] to compute checks from source values
+/// - keys_only: Compares keys only if true(see [`reconcile`](crate::conv::reconcile::reconcile))
+/// - cfg: Where and when to spill pairs
+/// - out: The destination of the JSON report
+#[cfg(all(feature = "json", feature = "async_tokio"))]
+#[allow(clippy::too_many_arguments)]
+pub async fn reconcile_buckets<C, D, W>(
+    t: &mut SelTgt<Channel>,
+    s: &mut SelSrc<Channel>,
+    i: InputBucket,
+    o: OutputBucket,
+    dl: Arc<D>,
+    cks: &C,
+    keys_only: bool,
+    cfg: &SpillConfig,
+    out: &mut W,
+) -> Result<Reconciled, Status>
+where
+    C: Checksum,
+    D: DeadLetter,
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    let obkt: Vec<u8> = o.bucket.clone();
+    let treq = TgtAll { bkt: Some(o) };
+    let tpairs = t.all(treq).await?.into_inner().map(|r| r.map(Ok));
+    let (tnoerr, trejecting) = reject_errs(tpairs, obkt, dl.clone());
+    let tsorted: Sorted = spill::sort(tnoerr.map(|a| (a.key, a.check)), cfg).await?;
+    let trejected: u64 = rejected_count(trejecting).await?;
+
+    let ibkt: Vec<u8> = i.bucket.clone();
+    let sreq = SelAll {
+        bkt: Some(i),
+        start_after: vec![],
+    };
+    let spairs = s.all(sreq).await?.into_inner().map(|r| r.map(inband));
+    let (snoerr, srejecting) = reject_errs(spairs, ibkt, dl);
+    let computed = snoerr.map(|a| {
+        let check: Vec<u8> = cks.check(&a.val);
+        (a.key, check)
+    });
+    let ssorted: Sorted = spill::sort(computed, cfg).await?;
+    let srejected: u64 = rejected_count(srejecting).await?;
+
+    reconcile_sorted(ssorted, tsorted, trejected + srejected, keys_only, out).await
+}
+
+#[cfg(test)]
+mod test_src2tgt {
+    mod upsert_selected {
+        use std::sync::Arc;
+
+        use tonic::transport::{Channel, Server};
+        use tonic::{Code, Status};
+
+        use crate::checksum::NoCheck;
+        use crate::conv::rpc::src2tgt::{upsert_selected, UpsertReport};
+        use crate::testing::rpc::{pair, serve, MemSource, MemTarget};
+        use crate::testing::MemDeadLetter;
+
+        use crate::rpc::fs2db::proto::source;
+        use source::v1::select_service_client::SelectServiceClient as SelSrc;
+        use source::v1::select_service_server::SelectServiceServer;
+        use source::v1::{InputBucket, Rejection};
+
+        use crate::rpc::fs2db::proto::target;
+        use target::v1::upsert_service_client::UpsertServiceClient;
+        use target::v1::upsert_service_server::UpsertServiceServer;
+        use target::v1::OutputBucket;
+
+        async fn run(
+            src: MemSource,
+        ) -> (
+            Result<UpsertReport, Status>,
+            Arc<MemTarget>,
+            Arc<MemDeadLetter>,
+        ) {
+            let tgt = Arc::new(MemTarget::default());
+            let ch: Channel = serve(
+                Server::builder()
+                    .add_service(SelectServiceServer::new(src))
+                    .add_service(UpsertServiceServer::from_arc(tgt.clone())),
+            )
+            .await;
+            let dl = Arc::new(MemDeadLetter::default());
+            let rslt = upsert_selected(
+                &mut UpsertServiceClient::new(ch.clone()),
+                &mut SelSrc::new(ch),
+                InputBucket {
+                    bucket: b"src".to_vec(),
+                },
+                OutputBucket {
+                    bucket: b"tgt".to_vec(),
+                },
+                dl.clone(),
+                Arc::new(NoCheck {}),
+            )
+            .await;
+            (rslt, tgt, dl)
+        }
+
+        #[tokio::test]
+        async fn inband() {
+            let mut bad = pair(b"k2", b"raw2");
+            bad.rejected = Some(Rejection {
+                code: Code::InvalidArgument.into(),
+                message: "bad row".into(),
+            });
+            let src = MemSource::new(vec![pair(b"k1", b"v1"), bad, pair(b"k3", b"v3")]);
+            let (rslt, tgt, dl) = run(src).await;
+            let expected = UpsertReport {
+                upserted: 2,
+                rejected: 1,
+            };
+            assert_eq!(rslt.unwrap(), expected);
+            assert_eq!(tgt.keys(), vec![b"k1".to_vec(), b"k3".to_vec()]);
+
+            let v = dl.rejected.lock().unwrap();
+            assert_eq!(
+                v.as_slice(),
+                &[(
+                    b"src".to_vec(),
+                    b"k2".to_vec(),
+                    b"raw2".to_vec(),
+                    Code::InvalidArgument
+                )]
+            );
+        }
+
+        #[tokio::test]
+        async fn truncated() {
+            let src = MemSource::new(vec![pair(b"k1", b"v1"), pair(b"k2", b"v2")]);
+            src.fails
+                .lock()
+                .unwrap()
+                .push_back(Some((1, Status::unavailable("source lost"))));
+            let (rslt, _tgt, dl) = run(src).await;
+            assert_eq!(rslt.unwrap_err().code(), Code::Unavailable);
+            assert!(dl.rejected.lock().unwrap().is_empty());
+        }
+    }
+
+    mod upsert_selected_resumable {
+        use std::sync::Arc;
+
+        use tonic::transport::{Channel, Server};
+        use tonic::{Code, Status};
+
+        use crate::checksum::NoCheck;
+        use crate::conv::checkpoint::Checkpoint;
+        use crate::conv::rpc::src2tgt::{upsert_selected_resumable, UpsertReport};
+        use crate::testing::rpc::{pair, serve, MemSource, MemTarget};
+        use crate::testing::{MemCheckpoint, MemDeadLetter};
+
+        use crate::rpc::fs2db::proto::source;
+        use source::v1::select_service_client::SelectServiceClient as SelSrc;
+        use source::v1::select_service_server::SelectServiceServer;
+        use source::v1::InputBucket;
+
+        use crate::rpc::fs2db::proto::target;
+        use target::v1::upsert_service_client::UpsertServiceClient;
+        use target::v1::upsert_service_server::UpsertServiceServer;
+        use target::v1::OutputBucket;
+
+        async fn run(ch: Channel, cp: &MemCheckpoint) -> Result<UpsertReport, Status> {
+            upsert_selected_resumable(
+                &mut UpsertServiceClient::new(ch.clone()),
+                &mut SelSrc::new(ch),
+                InputBucket {
+                    bucket: b"src".to_vec(),
+                },
+                OutputBucket {
+                    bucket: b"tgt".to_vec(),
+                },
+                Arc::new(MemDeadLetter::default()),
+                Arc::new(NoCheck {}),
+                cp,
+                2,
+            )
+            .await
+        }
+
+        #[tokio::test]
+        async fn resumed() {
+            let keys: Vec<&[u8]> = vec![b"k1", b"k2", b"k3", b"k4", b"k5"];
+            let src = Arc::new(MemSource::new(keys.iter().map(|k| pair(k, b"v")).collect()));
+            src.fails
+                .lock()
+                .unwrap()
+                .push_back(Some((3, Status::unavailable("source lost"))));
+            let tgt = Arc::new(MemTarget::default());
+            let ch: Channel = serve(
+                Server::builder()
+                    .add_service(SelectServiceServer::from_arc(src.clone()))
+                    .add_service(UpsertServiceServer::from_arc(tgt.clone())),
+            )
+            .await;
+            let cp = MemCheckpoint::default();
+
+            let e: Status = run(ch.clone(), &cp).await.unwrap_err();
+            assert_eq!(e.code(), Code::Unavailable);
+            let last = cp.last_key(b"src", b"tgt").await.unwrap();
+            assert_eq!(last, Some(b"k3".to_vec()));
+
+            let report: UpsertReport = run(ch.clone(), &cp).await.unwrap();
+            assert_eq!(report.upserted, 2);
+            assert_eq!(report.rejected, 0);
+
+            let expected: Vec<Vec<u8>> = keys.iter().map(|k| k.to_vec()).collect();
+            assert_eq!(tgt.keys(), expected);
+            assert_eq!(*src.calls.lock().unwrap(), vec![vec![], b"k3".to_vec()]);
+        }
+    }
+
+    #[cfg(feature = "async_tokio")]
+    mod upsert_selected_retried {
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        use tonic::transport::{Channel, Server};
+        use tonic::Status;
+
+        use crate::checksum::NoCheck;
+        use crate::conv::rpc::src2tgt::{upsert_selected_retried, UpsertReport};
+        use crate::retry::RetryPolicy;
+        use crate::testing::rpc::{pair, serve, MemSource, MemTarget};
+        use crate::testing::MemDeadLetter;
+
+        use crate::rpc::fs2db::proto::source;
+        use source::v1::select_service_client::SelectServiceClient as SelSrc;
+        use source::v1::select_service_server::SelectServiceServer;
+        use source::v1::InputBucket;
+
+        use crate::rpc::fs2db::proto::target;
+        use target::v1::upsert_service_client::UpsertServiceClient;
+        use target::v1::upsert_service_server::UpsertServiceServer;
+        use target::v1::OutputBucket;
+
+        #[tokio::test]
+        async fn mid_stream() {
+            let src = Arc::new(MemSource::new(vec![
+                pair(b"k1", b"v1"),
+                pair(b"k2", b"v2"),
+                pair(b"k3", b"v3"),
+            ]));
+            src.fails
+                .lock()
+                .unwrap()
+                .push_back(Some((2, Status::unavailable("source lost"))));
+            let tgt = Arc::new(MemTarget::default());
+            let ch: Channel = serve(
+                Server::builder()
+                    .add_service(SelectServiceServer::from_arc(src.clone()))
+                    .add_service(UpsertServiceServer::from_arc(tgt.clone())),
+            )
+            .await;
+            let policy = RetryPolicy {
+                initial: Duration::from_millis(1),
+                ..Default::default()
+            };
+            let report: UpsertReport = upsert_selected_retried(
+                &mut UpsertServiceClient::new(ch.clone()),
+                &mut SelSrc::new(ch),
+                InputBucket {
+                    bucket: b"src".to_vec(),
+                },
+                OutputBucket {
+                    bucket: b"tgt".to_vec(),
+                },
+                Arc::new(MemDeadLetter::default()),
+                Arc::new(NoCheck {}),
+                &policy,
+            )
+            .await
+            .unwrap();
+            assert_eq!(report.upserted, 3);
+            assert_eq!(src.calls.lock().unwrap().len(), 2);
+            assert_eq!(tgt.keys().len(), 3);
+        }
+    }
+
+    mod upsert_selected_batched {
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        use tonic::transport::{Channel, Server};
+
+        use crate::checksum::NoCheck;
+        use crate::conv::rpc::src2tgt::{upsert_selected_batched, BatchConfig, UpsertReport};
+        use crate::testing::rpc::{pair, serve, MemSource, MemTarget};
+        use crate::testing::MemDeadLetter;
+
+        use crate::rpc::fs2db::proto::source;
+        use source::v1::select_service_client::SelectServiceClient as SelSrc;
+        use source::v1::select_service_server::SelectServiceServer;
+        use source::v1::InputBucket;
+
+        use crate::rpc::fs2db::proto::target;
+        use target::v1::upsert_batch_service_client::UpsertBatchServiceClient;
+        use target::v1::upsert_batch_service_server::UpsertBatchServiceServer;
+        use target::v1::OutputBucket;
+
+        /// Returns the report and the sizes of the batches
+        async fn batches(src: MemSource, cfg: BatchConfig) -> (UpsertReport, Vec<usize>) {
+            let tgt = Arc::new(MemTarget::default());
+            let ch: Channel = serve(
+                Server::builder()
+                    .add_service(SelectServiceServer::new(src))
+                    .add_service(UpsertBatchServiceServer::from_arc(tgt.clone())),
+            )
+            .await;
+            let report: UpsertReport = upsert_selected_batched(
+                &mut UpsertBatchServiceClient::new(ch.clone()),
+                &mut SelSrc::new(ch),
+                InputBucket {
+                    bucket: b"src".to_vec(),
+                },
+                OutputBucket {
+                    bucket: b"tgt".to_vec(),
+                },
+                Arc::new(MemDeadLetter::default()),
+                Arc::new(NoCheck {}),
+                cfg,
+            )
+            .await
+            .unwrap();
+            let calls: Vec<usize> = tgt.calls.lock().unwrap().clone();
+            (report, calls)
+        }
+
+        fn pairs(cnt: u8) -> MemSource {
+            MemSource::new((0..cnt).map(|k| pair(&[k], b"v")).collect())
+        }
+
+        #[tokio::test]
+        async fn by_size() {
+            let cfg = BatchConfig {
+                size: 2,
+                interval: Duration::from_secs(60),
+            };
+            let (report, sizes) = batches(pairs(5), cfg).await;
+            assert_eq!(report.upserted, 5);
+            assert_eq!(sizes, vec![2, 2, 1]);
+        }
+
+        #[tokio::test]
+        async fn by_interval() {
+            let cfg = BatchConfig {
+                size: 100,
+                interval: Duration::from_millis(20),
+            };
+            let src = MemSource {
+                pause: Some((2, Duration::from_millis(300))),
+                ..pairs(3)
+            };
+            let (report, sizes) = batches(src, cfg).await;
+            assert_eq!(report.upserted, 3);
+            assert_eq!(sizes, vec![2, 1]);
+        }
+    }
+
+    mod drop_all_if_checks_match {
+        use std::sync::Arc;
+
+        use tonic::transport::{Channel, Server};
+
+        use crate::checksum::NoCheck;
+        use crate::conv::rpc::src2tgt::{drop_all_if_checks_match, CheckedDrop};
+        use crate::testing::rpc::{pair, serve, MemSource, MemTarget};
+        use crate::testing::MemDeadLetter;
+
+        use crate::rpc::fs2db::proto::source;
+        use source::v1::drop_service_client::DropServiceClient;
+        use source::v1::drop_service_server::DropServiceServer;
+        use source::v1::select_service_client::SelectServiceClient as SelSrc;
+        use source::v1::select_service_server::SelectServiceServer;
+        use source::v1::InputBucket;
+
+        use crate::rpc::fs2db::proto::target;
+        use target::v1::select_service_client::SelectServiceClient as SelTgt;
+        use target::v1::select_service_server::SelectServiceServer as SelTgtServer;
+        use target::v1::OutputBucket;
+
+        /// Checks a source(k1: new) without checks against a target(k1: old, check)
+        async fn checked(check: &[u8]) -> (CheckedDrop, Arc<MemSource>) {
+            let src = Arc::new(MemSource::new(vec![pair(b"k1", b"new")]));
+            let tgt = Arc::new(MemTarget::default());
+            tgt.upsert(vec![(b"k1".to_vec(), b"old".to_vec(), check.to_vec())]);
+            let ch: Channel = serve(
+                Server::builder()
+                    .add_service(SelectServiceServer::from_arc(src.clone()))
+                    .add_service(DropServiceServer::from_arc(src.clone()))
+                    .add_service(SelTgtServer::from_arc(tgt)),
+            )
+            .await;
+            let dropped: CheckedDrop = drop_all_if_checks_match(
+                &mut SelTgt::new(ch.clone()),
+                &mut SelSrc::new(ch.clone()),
+                &mut DropServiceClient::new(ch),
+                InputBucket {
+                    bucket: b"src".to_vec(),
+                },
+                OutputBucket {
+                    bucket: b"tgt".to_vec(),
+                },
+                Arc::new(MemDeadLetter::default()),
+                &NoCheck {},
+            )
+            .await
+            .unwrap();
+            (dropped, src)
+        }
+
+        #[tokio::test]
+        async fn empty_checks() {
+            let (dropped, src) = checked(b"crc-of-old").await;
+            match dropped {
+                CheckedDrop::Kept { mismatched, .. } => assert_eq!(mismatched.len(), 1),
+                CheckedDrop::Dropped(_) => panic!("dropped on key matches alone"),
+            }
+            assert!(src.dropped.lock().unwrap().is_empty());
+
+            let (dropped, src) = checked(b"").await;
+            assert!(matches!(dropped, CheckedDrop::Dropped(_)));
+            assert_eq!(*src.dropped.lock().unwrap(), vec![b"src".to_vec()]);
+        }
+    }
+    mod drop_dry_run {
+        use std::sync::atomic::Ordering;
+        use std::sync::Arc;
+
+        use tonic::transport::{Channel, Server};
+
+        use crate::checksum::NoCheck;
+        use crate::conv::rpc::src2tgt::drop_dry_run;
+        use crate::conv::verify::DiffSummary;
+        use crate::testing::rpc::{pair, serve, MemSource, MemTarget};
+        use crate::testing::MemDeadLetter;
+
+        use crate::rpc::fs2db::proto::source;
+        use source::v1::drop_service_server::DropServiceServer;
+        use source::v1::select_service_client::SelectServiceClient as SelSrc;
+        use source::v1::select_service_server::SelectServiceServer;
+        use source::v1::InputBucket;
+
+        use crate::rpc::fs2db::proto::target;
+        use target::v1::select_service_client::SelectServiceClient as SelTgt;
+        use target::v1::select_service_server::SelectServiceServer as SelTgtServer;
+        use target::v1::OutputBucket;
+
+        /// Compares a source(k1, k2) with a target(k1 with a check, k3)
+        async fn compared(keys_only: bool) -> (DiffSummary, Arc<MemSource>) {
+            let src = Arc::new(MemSource::new(vec![pair(b"k1", b"v1"), pair(b"k2", b"v2")]));
+            let tgt = Arc::new(MemTarget::default());
+            tgt.upsert(vec![
+                (b"k1".to_vec(), b"v1".to_vec(), b"c1".to_vec()),
+                (b"k3".to_vec(), b"v3".to_vec(), vec![]),
+            ]);
+            let ch: Channel = serve(
+                Server::builder()
+                    .add_service(SelectServiceServer::from_arc(src.clone()))
+                    .add_service(DropServiceServer::from_arc(src.clone()))
+                    .add_service(SelTgtServer::from_arc(tgt)),
+            )
+            .await;
+            let summary: DiffSummary = drop_dry_run(
+                &mut SelTgt::new(ch.clone()),
+                &mut SelSrc::new(ch),
+                InputBucket {
+                    bucket: b"src".to_vec(),
+                },
+                OutputBucket {
+                    bucket: b"tgt".to_vec(),
+                },
+                Arc::new(MemDeadLetter::default()),
+                &NoCheck {},
+                keys_only,
+            )
+            .await
+            .unwrap();
+            (summary, src)
+        }
+
+        #[tokio::test]
+        async fn never_dropped() {
+            let (s, src) = compared(false).await;
+            assert_eq!(s.source_count, 2);
+            assert_eq!(s.target_count, 2);
+            assert_eq!(s.matched, 0);
+            assert_eq!(s.missing_in_target, vec![b"k2".to_vec()]);
+            assert_eq!(s.extra_in_target, vec![b"k3".to_vec()]);
+            assert_eq!(s.check_mismatched.len(), 1);
+            assert_eq!(s.check_mismatched[0].key, b"k1");
+            assert!(!s.verified());
+            assert_eq!(src.checked.load(Ordering::SeqCst), 0);
+
+            let (s, src) = compared(true).await;
+            assert_eq!(s.matched, 1);
+            assert!(s.check_mismatched.is_empty());
+            assert_eq!(src.checked.load(Ordering::SeqCst), 0);
+        }
+    }
 }
@@ -14,8 +14,9 @@ pub async fn upsert_selected<I, O, S, U, C>(
 ) -> Result<u64, Status>
 where
     S: Select<Bucket = I>,
+    S::Rows: Send,
     U: Upsert<Bucket = O>,
-    C: Fn(<S as Select>::Row) -> Result<<U as Upsert>::Row, Status>,
+    C: Sync + Fn(<S as Select>::Row) -> Result<<U as Upsert>::Row, Status>,
 {
     let rows = sel.all(ibucket).await?;
 
@@ -0,0 +1,244 @@
+//! Compares checks of key/val pairs in a source bucket and a target bucket
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use futures::Stream;
+use futures::StreamExt;
+
+/// A key which is missing in a bucket or has a different check
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub key: Vec<u8>,
+
+    /// The check computed from the source value(None: missing in the source)
+    pub source: Option<Vec<u8>>,
+
+    /// The check returned by the target(None: missing in the target)
+    pub target: Option<Vec<u8>>,
+}
+
+/// The result of [`compare_checks`]
+#[derive(Debug, Clone, Default)]
+pub struct Compared {
+    /// Number of keys in the source
+    pub source_count: u64,
+
+    /// Number of keys in the target
+    pub target_count: u64,
+
+    /// Keys found in both buckets with the same check(the check of the target)
+    pub matched: BTreeMap<Vec<u8>, Vec<u8>>,
+
+    /// Missing keys and keys with different checks(sorted by key)
+    pub mismatched: Vec<Mismatch>,
+
+    /// Keys found more than once in the source(a key per extra copy, sorted)
+    pub duplicated: Vec<Vec<u8>>,
+}
+
+/// Compares key/check pairs of a source with key/check pairs of a target.
+///
+/// The first pair of a key in the source is compared; later copies are reported as duplicated.
+///
+/// ## Arguments
+/// - source: key/check pairs computed from a source bucket
+/// - target: key/check pairs returned by a target bucket
+/// - keys_only: An empty source check(e.g, [`NoCheck`](crate::checksum::NoCheck)) matches any
+///   target check if true(only keys are compared); must be false to decide a drop
+pub async fn compare_checks<S>(
+    source: S,
+    target: BTreeMap<Vec<u8>, Vec<u8>>,
+    keys_only: bool,
+) -> Compared
+where
+    S: Stream<Item = (Vec<u8>, Vec<u8>)>,
+{
+    let init = Compared {
+        target_count: target.len() as u64,
+        ..Default::default()
+    };
+    let seen: BTreeSet<Vec<u8>> = BTreeSet::new();
+    let (mut compared, rest, _) = source
+        .fold((init, target, seen), |state, pair| async move {
+            let (mut c, mut rest, mut seen) = state;
+            let (key, check) = pair;
+            c.source_count += 1;
+            if !seen.insert(key.clone()) {
+                c.duplicated.push(key);
+                return (c, rest, seen);
+            }
+            match rest.remove(&key) {
+                Some(t) if t == check || (keys_only && check.is_empty()) => {
+                    c.matched.insert(key, t);
+                }
+                t => c.mismatched.push(Mismatch {
+                    key,
+                    source: Some(check),
+                    target: t,
+                }),
+            }
+            (c, rest, seen)
+        })
+        .await;
+    let extra = rest.into_iter().map(|pair| {
+        let (key, check) = pair;
+        Mismatch {
+            key,
+            source: None,
+            target: Some(check),
+        }
+    });
+    compared.mismatched.extend(extra);
+    compared.mismatched.sort_by(|a, b| a.key.cmp(&b.key));
+    compared.duplicated.sort();
+    compared
+}
+
+/// Counts and keys of a comparison
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffSummary {
+    /// Number of keys in the source
+    pub source_count: u64,
+
+    /// Number of keys in the target
+    pub target_count: u64,
+
+    /// Number of keys found in both buckets with the same check
+    pub matched: u64,
+
+    /// Keys found in the source but not in the target
+    pub missing_in_target: Vec<Vec<u8>>,
+
+    /// Keys found in the target but not in the source
+    pub extra_in_target: Vec<Vec<u8>>,
+
+    /// Keys found in both buckets with different checks
+    pub check_mismatched: Vec<Mismatch>,
+
+    /// Keys found more than once in the source(a key per extra copy)
+    pub duplicated: Vec<Vec<u8>>,
+
+    /// Number of pairs which could not be compared(e.g, rejected pairs)
+    pub rejected: u64,
+}
+
+impl DiffSummary {
+    /// Creates [`DiffSummary`] from [`Compared`] and number of rejected pairs
+    pub fn new(c: Compared, rejected: u64) -> Self {
+        let init = Self {
+            source_count: c.source_count,
+            target_count: c.target_count,
+            matched: c.matched.len() as u64,
+            duplicated: c.duplicated,
+            rejected,
+            ..Default::default()
+        };
+        c.mismatched.into_iter().fold(init, |mut s, m| {
+            match (&m.source, &m.target) {
+                (Some(_), None) => s.missing_in_target.push(m.key),
+                (None, Some(_)) => s.extra_in_target.push(m.key),
+                _ => s.check_mismatched.push(m),
+            }
+            s
+        })
+    }
+
+    /// Checks if the source bucket can be dropped(all keys verified)
+    pub fn verified(&self) -> bool {
+        self.missing_in_target.is_empty()
+            && self.extra_in_target.is_empty()
+            && self.check_mismatched.is_empty()
+            && self.duplicated.is_empty()
+            && 0 == self.rejected
+    }
+}
+
+#[cfg(test)]
+mod test_verify {
+    mod compare_checks {
+        use std::collections::BTreeMap;
+
+        use crate::conv::verify::{compare_checks, Compared, DiffSummary, Mismatch};
+
+        fn pair(k: &[u8], c: &[u8]) -> (Vec<u8>, Vec<u8>) {
+            (k.to_vec(), c.to_vec())
+        }
+
+        #[tokio::test]
+        async fn mismatched() {
+            let source = futures::stream::iter(vec![
+                pair(b"k1", b"c1"),
+                pair(b"k2", b"c2"),
+                pair(b"k3", b"c3"),
+            ]);
+            let target = BTreeMap::from_iter(vec![
+                pair(b"k0", b"c0"),
+                pair(b"k1", b"c1"),
+                pair(b"k2", b"XX"),
+            ]);
+            let c: Compared = compare_checks(source, target, false).await;
+            assert_eq!(c.source_count, 3);
+            assert_eq!(c.target_count, 3);
+            assert_eq!(c.matched.len(), 1);
+            assert_eq!(
+                c.mismatched,
+                vec![
+                    Mismatch {
+                        key: b"k0".to_vec(),
+                        source: None,
+                        target: Some(b"c0".to_vec()),
+                    },
+                    Mismatch {
+                        key: b"k2".to_vec(),
+                        source: Some(b"c2".to_vec()),
+                        target: Some(b"XX".to_vec()),
+                    },
+                    Mismatch {
+                        key: b"k3".to_vec(),
+                        source: Some(b"c3".to_vec()),
+                        target: None,
+                    },
+                ]
+            );
+
+            let summary = DiffSummary::new(c, 0);
+            assert_eq!(summary.matched, 1);
+            assert_eq!(summary.missing_in_target, vec![b"k3".to_vec()]);
+            assert_eq!(summary.extra_in_target, vec![b"k0".to_vec()]);
+            assert_eq!(summary.check_mismatched.len(), 1);
+            assert!(!summary.verified());
+        }
+
+        #[tokio::test]
+        async fn keys_only() {
+            let source = || futures::stream::iter(vec![pair(b"k1", b"")]);
+            let target = || BTreeMap::from_iter(vec![pair(b"k1", b"c1")]);
+            let c: Compared = compare_checks(source(), target(), true).await;
+            assert!(DiffSummary::new(c, 0).verified());
+
+            let c: Compared = compare_checks(source(), target(), false).await;
+            assert!(c.matched.is_empty());
+            assert!(!DiffSummary::new(c, 0).verified());
+        }
+
+        #[tokio::test]
+        async fn duplicated() {
+            let source = futures::stream::iter(vec![
+                pair(b"k1", b"c1"),
+                pair(b"k2", b"c2"),
+                pair(b"k1", b"c1"),
+            ]);
+            let target = BTreeMap::from_iter(vec![pair(b"k1", b"c1"), pair(b"k2", b"c2")]);
+            let c: Compared = compare_checks(source, target, false).await;
+            assert_eq!(c.source_count, 3);
+            assert_eq!(c.matched.len(), 2);
+            assert!(c.mismatched.is_empty());
+            assert_eq!(c.duplicated, vec![b"k1".to_vec()]);
+
+            let summary = DiffSummary::new(c, 0);
+            assert!(summary.missing_in_target.is_empty());
+            assert_eq!(summary.duplicated, vec![b"k1".to_vec()]);
+            assert!(!summary.verified());
+        }
+    }
+}
@@ -0,0 +1,6 @@
+//! Hex encoding for filenames and reports
+
+/// Encodes bytes as lowercase hex
+pub(crate) fn encode(b: &[u8]) -> String {
+    b.iter().map(|u| format!("{u:02x}")).collect()
+}
@@ -16,3 +16,15 @@ pub mod merge;
 pub mod rpc;
 
 pub mod lines;
+
+#[cfg(feature = "csv_tokio_async")]
+pub mod delimited;
+
+#[cfg(feature = "parquet")]
+pub mod pq;
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
+
+#[cfg(feature = "walk")]
+pub mod walk;
@@ -0,0 +1,157 @@
+//! A [`BucketSource`] which reads record batches of Arrow IPC files
+//!
+//! Both the file format(Feather v2) and the stream format are supported;
+//! the format is detected by the magic bytes of the file format.
+
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+use tokio_stream::wrappers::ReceiverStream;
+
+use tonic::Status;
+
+use arrow_array::RecordBatch;
+use arrow_ipc::reader::{FileReader, StreamReader};
+
+use crate::input::conv::lines::FsSource;
+use crate::input::source::BucketSource;
+
+/// Magic bytes of the Arrow IPC file format
+pub const FILE_MAGIC: &[u8] = b"ARROW1";
+
+fn status<E>(e: E) -> Status
+where
+    E: core::fmt::Display,
+{
+    Status::internal(format!("unable to read an arrow ipc file: {e}"))
+}
+
+fn read_all(
+    f: File,
+    tx: &tokio::sync::mpsc::Sender<Result<(usize, RecordBatch), Status>>,
+) -> Result<(), Status> {
+    let mut br = BufReader::new(f);
+    let mut magic: Vec<u8> = Vec::with_capacity(FILE_MAGIC.len());
+    (&mut br)
+        .take(FILE_MAGIC.len() as u64)
+        .read_to_end(&mut magic)
+        .map_err(status)?;
+    br.seek(SeekFrom::Start(0)).map_err(status)?;
+    let batches: Box<dyn Iterator<Item = Result<RecordBatch, _>>> = match magic == FILE_MAGIC {
+        true => Box::new(FileReader::try_new(br, None).map_err(status)?),
+        false => Box::new(StreamReader::try_new(br, None).map_err(status)?),
+    };
+    for (ix, rslt) in batches.enumerate() {
+        let item = rslt.map(|b| (ix, b)).map_err(status);
+        if tx.blocking_send(item).is_err() {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+pub struct ArrowIpcSrc<F> {
+    fsrc: F,
+}
+
+#[tonic::async_trait]
+impl<F> BucketSource for ArrowIpcSrc<F>
+where
+    F: FsSource,
+{
+    type Bucket = F::Bucket;
+    type K = usize;
+    type V = RecordBatch;
+    type All = ReceiverStream<Result<(Self::K, Self::V), Status>>;
+
+    /// Gets all record batches with their indices
+    async fn get_all_by_bucket(&self, b: Self::Bucket) -> Result<Self::All, Status> {
+        let f: File = self.fsrc.get_file_by_bucket(b).await?.into_std().await;
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = read_all(f, &tx) {
+                let _ = tx.blocking_send(Err(e));
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+}
+
+/// Creates a [`BucketSource`] which reads an Arrow IPC file(file or stream format) per bucket.
+///
+/// Rows are not split; a value is a whole [`RecordBatch`].
+pub fn arrow_ipc_src_new<F>(
+    fsrc: F,
+) -> impl BucketSource<Bucket = F::Bucket, K = usize, V = RecordBatch>
+where
+    F: FsSource,
+{
+    ArrowIpcSrc { fsrc }
+}
+
+#[cfg(test)]
+mod test_arrow {
+    mod arrow_ipc_src_new {
+        use std::fs::File;
+        use std::path::Path;
+        use std::sync::Arc;
+
+        use futures::StreamExt;
+
+        use tonic::Status;
+
+        use arrow_array::{ArrayRef, Int64Array, RecordBatch};
+        use arrow_ipc::writer::{FileWriter, StreamWriter};
+
+        use crate::input::arrow::arrow_ipc_src_new;
+        use crate::input::source::BucketSource;
+        use crate::testing::TempDir;
+
+        fn batch(ids: Vec<i64>) -> RecordBatch {
+            RecordBatch::try_from_iter(vec![("id", Arc::new(Int64Array::from(ids)) as ArrayRef)])
+                .unwrap()
+        }
+
+        fn batches() -> Vec<RecordBatch> {
+            vec![batch(vec![1, 2]), batch(vec![3])]
+        }
+
+        fn write_file(p: &Path) {
+            let f = File::create(p).unwrap();
+            let mut w = FileWriter::try_new(f, &batch(vec![]).schema()).unwrap();
+            batches().iter().for_each(|b| w.write(b).unwrap());
+            w.finish().unwrap();
+        }
+
+        fn write_stream(p: &Path) {
+            let f = File::create(p).unwrap();
+            let mut w = StreamWriter::try_new(f, &batch(vec![]).schema()).unwrap();
+            batches().iter().for_each(|b| w.write(b).unwrap());
+            w.finish().unwrap();
+        }
+
+        #[tokio::test]
+        async fn file_and_stream() {
+            let dir = TempDir::new("arrow-src");
+            write_file(&dir.join("b.arrow"));
+            write_stream(&dir.join("b.arrows"));
+            std::fs::write(dir.join("b.txt"), b"not arrow").unwrap();
+
+            let src = arrow_ipc_src_new(dir.dir());
+            for name in ["b.arrow", "b.arrows"] {
+                let all = src.get_all_by_bucket(name).await.unwrap();
+                let got: Vec<(usize, RecordBatch)> = all.map(|r| r.unwrap()).collect().await;
+                let expected: Vec<(usize, RecordBatch)> =
+                    batches().into_iter().enumerate().collect();
+                assert_eq!(got, expected, "{name}");
+            }
+
+            let all = src.get_all_by_bucket("b.txt").await.unwrap();
+            let got: Vec<Result<(usize, RecordBatch), Status>> = all.collect().await;
+            assert_eq!(got.len(), 1);
+            assert!(got[0].is_err());
+
+            assert!(src.get_all_by_bucket("none.arrow").await.is_err());
+        }
+    }
+}
@@ -1,3 +1,27 @@
-//! A module to make a grouped row
+//! A module to make a grouped row and to read binary records
+
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 pub mod mem;
+
+pub mod fixed;
+pub mod framed;
+
+#[cfg(feature = "grpc_tonic")]
+pub mod pb;
+
+/// Reads bytes until the buffer is full or EOF; returns the number of bytes read
+pub(crate) async fn read_full<R>(r: &mut R, buf: &mut [u8]) -> std::io::Result<usize>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut tot: usize = 0;
+    while tot < buf.len() {
+        let cnt: usize = r.read(&mut buf[tot..]).await?;
+        if 0 == cnt {
+            break;
+        }
+        tot += cnt;
+    }
+    Ok(tot)
+}
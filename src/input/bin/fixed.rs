@@ -0,0 +1,256 @@
+//! A [`BucketSource`] which reads fixed-width records
+
+use std::sync::Arc;
+
+use tokio::io::BufReader;
+
+use tokio_stream::wrappers::ReceiverStream;
+
+use tonic::Status;
+
+use crate::input::bin::read_full;
+use crate::input::conv::lines::ReadSource;
+use crate::input::source::BucketSource;
+
+/// A field of a fixed-width record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldSpec {
+    pub name: String,
+
+    /// The offset of the field in a record
+    pub offset: usize,
+
+    pub len: usize,
+}
+
+/// Layout of fixed-width records
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixedLayout {
+    /// The length of a record(including padding or a newline after the fields if any)
+    pub record_len: usize,
+
+    pub fields: Vec<FieldSpec>,
+
+    /// Trailing bytes of a field to remove(e.g, `Some(b' ')` for space padded text)
+    pub pad: Option<u8>,
+}
+
+impl FixedLayout {
+    /// Checks that all fields are in a record
+    pub fn validate(&self) -> Result<(), Status> {
+        if 0 == self.record_len {
+            return Err(Status::invalid_argument("empty record"));
+        }
+        let out: Option<&FieldSpec> = self.fields.iter().find(|f| {
+            f.offset
+                .checked_add(f.len)
+                .map(|end| self.record_len < end)
+                .unwrap_or(true)
+        });
+        match out {
+            None => Ok(()),
+            Some(f) => Err(Status::invalid_argument(format!(
+                "field out of a record: {}",
+                f.name
+            ))),
+        }
+    }
+
+    /// Gets a [`FixedKey`] which uses the named field as a key
+    pub fn key_field(&self, name: &str) -> Result<FixedField, Status> {
+        self.fields
+            .iter()
+            .position(|f| f.name == name)
+            .map(|ix| FixedField { ix })
+            .ok_or_else(|| Status::invalid_argument(format!("no such field: {name}")))
+    }
+
+    fn split(&self, rec: &[u8]) -> Vec<Vec<u8>> {
+        self.fields
+            .iter()
+            .map(|f| {
+                let mut field: &[u8] = &rec[f.offset..f.offset + f.len];
+                if let Some(pad) = self.pad {
+                    while let Some((last, rest)) = field.split_last() {
+                        if *last != pad {
+                            break;
+                        }
+                        field = rest;
+                    }
+                }
+                field.to_vec()
+            })
+            .collect()
+    }
+}
+
+/// Gets a key from fields of a record
+pub trait FixedKey: Send + Sync + 'static {
+    type K: Send + Sync + 'static;
+
+    /// Gets a key from the index of the record and its fields
+    fn key(&self, ix: u64, fields: &[Vec<u8>]) -> Result<Self::K, Status>;
+}
+
+/// Uses the index of a record as a key
+#[derive(Clone, Copy, Default)]
+pub struct FixedIndex {}
+
+impl FixedKey for FixedIndex {
+    type K = u64;
+
+    fn key(&self, ix: u64, _: &[Vec<u8>]) -> Result<u64, Status> {
+        Ok(ix)
+    }
+}
+
+/// Uses a field as a key(see [`FixedLayout::key_field`])
+#[derive(Clone, Copy)]
+pub struct FixedField {
+    ix: usize,
+}
+
+impl FixedKey for FixedField {
+    type K = Vec<u8>;
+
+    fn key(&self, _: u64, fields: &[Vec<u8>]) -> Result<Vec<u8>, Status> {
+        fields
+            .get(self.ix)
+            .cloned()
+            .ok_or_else(|| Status::internal("key field missing"))
+    }
+}
+
+pub struct FixedSrc<R, K> {
+    rsrc: R,
+    layout: Arc<FixedLayout>,
+    key: Arc<K>,
+}
+
+#[tonic::async_trait]
+impl<R, K> BucketSource for FixedSrc<R, K>
+where
+    R: ReadSource,
+    K: FixedKey,
+{
+    type Bucket = R::Bucket;
+    type K = K::K;
+    type V = Vec<Vec<u8>>;
+    type All = ReceiverStream<Result<(Self::K, Self::V), Status>>;
+
+    /// Gets all records(fields in the order of the layout).
+    ///
+    /// A partial record at the end is reported as [`tonic::Code::DataLoss`].
+    async fn get_all_by_bucket(&self, b: Self::Bucket) -> Result<Self::All, Status> {
+        let r: R::R = self.rsrc.get_src_read_by_bucket(b).await?;
+        let layout: Arc<FixedLayout> = self.layout.clone();
+        let key: Arc<K> = self.key.clone();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        tokio::spawn(async move {
+            let mut br = BufReader::new(r);
+            let mut rec: Vec<u8> = vec![0; layout.record_len];
+            for ix in 0.. {
+                let offset: u64 = ix * layout.record_len as u64;
+                let item: Result<(K::K, Vec<Vec<u8>>), Status> =
+                    match read_full(&mut br, &mut rec).await {
+                        Ok(0) => return,
+                        Ok(cnt) if cnt < rec.len() => Err(Status::data_loss(format!(
+                            "partial record at {offset}: {cnt} bytes"
+                        ))),
+                        Ok(_) => {
+                            let fields: Vec<Vec<u8>> = layout.split(&rec);
+                            key.key(ix, &fields).map(|k| (k, fields))
+                        }
+                        Err(e) => Err(Status::internal(format!(
+                            "unable to read a record at {offset}: {e}"
+                        ))),
+                    };
+                let stop: bool = item.is_err();
+                if tx.send(item).await.is_err() || stop {
+                    return;
+                }
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+}
+
+/// Creates a [`BucketSource`] which reads fixed-width records from a [`ReadSource`].
+///
+/// ## Arguments
+/// - rsrc: A [`ReadSource`] which has fixed-width records
+/// - layout: The [`FixedLayout`] of the records(fails if invalid)
+/// - key: A [`FixedKey`](e.g, [`FixedIndex`], [`FixedField`]) to get a key from a record
+pub fn fixed_src_new<R, K>(
+    rsrc: R,
+    layout: FixedLayout,
+    key: K,
+) -> Result<impl BucketSource<Bucket = R::Bucket, K = K::K, V = Vec<Vec<u8>>>, Status>
+where
+    R: ReadSource,
+    K: FixedKey,
+{
+    layout.validate()?;
+    Ok(FixedSrc {
+        rsrc,
+        layout: Arc::new(layout),
+        key: Arc::new(key),
+    })
+}
+
+#[cfg(test)]
+mod test_fixed {
+    mod fixed_src_new {
+        use futures::StreamExt;
+
+        use tonic::{Code, Status};
+
+        use crate::input::bin::fixed::{fixed_src_new, FieldSpec, FixedIndex, FixedLayout};
+        use crate::input::source::BucketSource;
+        use crate::testing::mem_new;
+
+        /// Fields of a record
+        type Fields = Vec<Vec<u8>>;
+
+        fn layout() -> FixedLayout {
+            let field = |name: &str, offset: usize, len: usize| FieldSpec {
+                name: name.into(),
+                offset,
+                len,
+            };
+            FixedLayout {
+                record_len: 9,
+                fields: vec![field("id", 0, 3), field("name", 3, 5)],
+                pad: Some(b' '),
+            }
+        }
+
+        #[tokio::test]
+        async fn records() {
+            let l: FixedLayout = layout();
+            let key = l.key_field("id").unwrap();
+            let src = fixed_src_new(mem_new(), l, key).unwrap();
+            let all = src.get_all_by_bucket(b"001ab   \n002cdefg\n").await;
+            let got: Vec<(Vec<u8>, Fields)> = all.unwrap().map(|r| r.unwrap()).collect().await;
+            assert_eq!(
+                got,
+                vec![
+                    (b"001".to_vec(), vec![b"001".to_vec(), b"ab".to_vec()]),
+                    (b"002".to_vec(), vec![b"002".to_vec(), b"cdefg".to_vec()]),
+                ]
+            );
+
+            let src = fixed_src_new(mem_new(), layout(), FixedIndex {}).unwrap();
+            let all = src.get_all_by_bucket(b"001ab   \n002").await;
+            let got: Vec<Result<(u64, Fields), Status>> = all.unwrap().collect().await;
+            assert_eq!(got.len(), 2);
+            assert_eq!(got[0].as_ref().unwrap().0, 0);
+            assert_eq!(got[1].as_ref().unwrap_err().code(), Code::DataLoss);
+
+            let mut bad: FixedLayout = layout();
+            bad.record_len = 7;
+            assert!(fixed_src_new(mem_new::<&[u8]>(), bad, FixedIndex {}).is_err());
+            assert!(layout().key_field("none").is_err());
+        }
+    }
+}
@@ -0,0 +1,278 @@
+//! A [`BucketSource`] which reads length-prefixed frames
+
+use std::sync::Arc;
+
+use tokio::io::{AsyncRead, BufReader};
+
+use tokio_stream::wrappers::ReceiverStream;
+
+use tonic::Status;
+
+use crate::input::bin::read_full;
+use crate::input::conv::lines::ReadSource;
+use crate::input::source::BucketSource;
+
+/// The default max length of a frame(64 MiB)
+pub const DEFAULT_MAX_FRAME_LEN: u64 = 64 << 20;
+
+/// Byte order of a length prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Big,
+    Little,
+}
+
+/// The length prefix of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LengthPrefix {
+    U16(Endian),
+    U32(Endian),
+    U64(Endian),
+
+    /// Unsigned LEB128(e.g, length-delimited protobuf messages)
+    Varint,
+}
+
+/// Format of frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameConfig {
+    pub prefix: LengthPrefix,
+
+    /// Frames longer than this are rejected before being read
+    pub max_len: u64,
+}
+
+impl FrameConfig {
+    /// Creates a config which accepts frames up to [`DEFAULT_MAX_FRAME_LEN`]
+    pub fn new(prefix: LengthPrefix) -> Self {
+        Self {
+            prefix,
+            max_len: DEFAULT_MAX_FRAME_LEN,
+        }
+    }
+}
+
+/// The error of reading a length prefix
+enum PrefixErr {
+    Io(std::io::Error),
+
+    /// EOF in the middle of a prefix
+    Partial,
+
+    Overflow,
+}
+
+async fn read_varint<R>(r: &mut R) -> Result<Option<(u64, u64)>, PrefixErr>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut v: u64 = 0;
+    for ix in 0..10 {
+        let mut b: [u8; 1] = [0];
+        match read_full(r, &mut b).await.map_err(PrefixErr::Io)? {
+            0 if 0 == ix => return Ok(None),
+            0 => return Err(PrefixErr::Partial),
+            _ => {}
+        }
+        let low: u64 = (b[0] & 0x7f).into();
+        if 9 == ix && 1 < low {
+            return Err(PrefixErr::Overflow);
+        }
+        v |= low << (7 * ix);
+        if b[0] < 0x80 {
+            return Ok(Some((v, ix + 1)));
+        }
+    }
+    Err(PrefixErr::Overflow)
+}
+
+impl LengthPrefix {
+    /// Reads a length and the length of the prefix(None: EOF before the prefix)
+    async fn read<R>(&self, r: &mut R) -> Result<Option<(u64, u64)>, PrefixErr>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let (sz, endian): (usize, Endian) = match self {
+            Self::Varint => return read_varint(r).await,
+            Self::U16(e) => (2, *e),
+            Self::U32(e) => (4, *e),
+            Self::U64(e) => (8, *e),
+        };
+        let mut b: [u8; 8] = [0; 8];
+        let prefix: &mut [u8] = &mut b[..sz];
+        match read_full(r, prefix).await.map_err(PrefixErr::Io)? {
+            0 => return Ok(None),
+            cnt if cnt < sz => return Err(PrefixErr::Partial),
+            _ => {}
+        }
+        let len: u64 = prefix.iter().enumerate().fold(0, |tot, pair| {
+            let (ix, byte) = pair;
+            let shift: usize = match endian {
+                Endian::Big => 8 * (sz - 1 - ix),
+                Endian::Little => 8 * ix,
+            };
+            tot | (u64::from(*byte) << shift)
+        });
+        Ok(Some((len, sz as u64)))
+    }
+}
+
+/// Reads a frame at the offset(None: no more frame); returns the frame and its total length
+async fn read_frame<R>(
+    r: &mut R,
+    cfg: &FrameConfig,
+    offset: u64,
+) -> Result<Option<(Vec<u8>, u64)>, Status>
+where
+    R: AsyncRead + Unpin,
+{
+    let (len, plen): (u64, u64) = match cfg.prefix.read(r).await {
+        Ok(None) => return Ok(None),
+        Ok(Some(pair)) => pair,
+        Err(PrefixErr::Io(e)) => {
+            return Err(Status::internal(format!(
+                "unable to read a frame at {offset}: {e}"
+            )))
+        }
+        Err(PrefixErr::Partial) => {
+            return Err(Status::data_loss(format!("partial prefix at {offset}")))
+        }
+        Err(PrefixErr::Overflow) => {
+            return Err(Status::data_loss(format!("invalid varint at {offset}")))
+        }
+    };
+    if cfg.max_len < len {
+        return Err(Status::resource_exhausted(format!(
+            "a frame at {offset} longer than {} bytes: {len}",
+            cfg.max_len
+        )));
+    }
+    let mut frame: Vec<u8> = vec![0; len as usize];
+    let cnt: usize = read_full(r, &mut frame)
+        .await
+        .map_err(|e| Status::internal(format!("unable to read a frame at {offset}: {e}")))?;
+    match cnt < frame.len() {
+        true => Err(Status::data_loss(format!(
+            "partial frame at {offset}: {cnt} of {len} bytes"
+        ))),
+        false => Ok(Some((frame, plen + len))),
+    }
+}
+
+pub struct FramedSrc<R> {
+    rsrc: R,
+    cfg: Arc<FrameConfig>,
+}
+
+#[tonic::async_trait]
+impl<R> BucketSource for FramedSrc<R>
+where
+    R: ReadSource,
+{
+    type Bucket = R::Bucket;
+    type K = u64;
+    type V = Vec<u8>;
+    type All = ReceiverStream<Result<(Self::K, Self::V), Status>>;
+
+    /// Gets all frames(without prefixes) with their indices
+    async fn get_all_by_bucket(&self, b: Self::Bucket) -> Result<Self::All, Status> {
+        let r: R::R = self.rsrc.get_src_read_by_bucket(b).await?;
+        let cfg: Arc<FrameConfig> = self.cfg.clone();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        tokio::spawn(async move {
+            let mut br = BufReader::new(r);
+            let mut offset: u64 = 0;
+            for ix in 0.. {
+                let item = match read_frame(&mut br, &cfg, offset).await {
+                    Ok(None) => return,
+                    Ok(Some((frame, len))) => {
+                        offset += len;
+                        Ok((ix, frame))
+                    }
+                    Err(e) => Err(e),
+                };
+                let stop: bool = item.is_err();
+                if tx.send(item).await.is_err() || stop {
+                    return;
+                }
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+}
+
+/// Creates a [`BucketSource`] which reads length-prefixed frames from a [`ReadSource`]
+pub fn framed_src_new<R>(
+    rsrc: R,
+    cfg: FrameConfig,
+) -> impl BucketSource<Bucket = R::Bucket, K = u64, V = Vec<u8>>
+where
+    R: ReadSource,
+{
+    FramedSrc {
+        rsrc,
+        cfg: Arc::new(cfg),
+    }
+}
+
+#[cfg(test)]
+mod test_framed {
+    mod framed_src_new {
+        use futures::StreamExt;
+
+        use tonic::{Code, Status};
+
+        use crate::input::bin::framed::{framed_src_new, Endian, FrameConfig, LengthPrefix};
+        use crate::input::source::BucketSource;
+        use crate::testing::mem_new;
+
+        async fn frames(cfg: FrameConfig, raw: Vec<u8>) -> Vec<Result<(u64, Vec<u8>), Status>> {
+            let src = framed_src_new(mem_new(), cfg);
+            src.get_all_by_bucket(raw).await.unwrap().collect().await
+        }
+
+        fn ok(got: Vec<Result<(u64, Vec<u8>), Status>>) -> Vec<Vec<u8>> {
+            got.into_iter().map(|r| r.unwrap().1).collect()
+        }
+
+        #[tokio::test]
+        async fn prefixes() {
+            let big = FrameConfig::new(LengthPrefix::U16(Endian::Big));
+            let got = frames(big, b"\x00\x02ab\x00\x00\x00\x01c".to_vec()).await;
+            assert_eq!(ok(got), vec![b"ab".to_vec(), vec![], b"c".to_vec()]);
+
+            let little = FrameConfig::new(LengthPrefix::U32(Endian::Little));
+            let got = frames(little, b"\x03\x00\x00\x00xyz".to_vec()).await;
+            assert_eq!(ok(got), vec![b"xyz".to_vec()]);
+
+            let u64be = FrameConfig::new(LengthPrefix::U64(Endian::Big));
+            let got = frames(u64be, b"\0\0\0\0\0\0\0\x01z".to_vec()).await;
+            assert_eq!(ok(got), vec![b"z".to_vec()]);
+
+            let mut raw: Vec<u8> = vec![0xac, 0x02];
+            raw.extend(vec![b'v'; 300]);
+            raw.extend(b"\x01w");
+            let got = frames(FrameConfig::new(LengthPrefix::Varint), raw).await;
+            assert_eq!(ok(got), vec![vec![b'v'; 300], b"w".to_vec()]);
+        }
+
+        #[tokio::test]
+        async fn broken() {
+            let cfg = FrameConfig::new(LengthPrefix::U32(Endian::Big));
+            let got = frames(cfg, b"\x00\x00\x00\x01a\x00\x00".to_vec()).await;
+            assert_eq!(got.len(), 2);
+            assert_eq!(got[1].as_ref().unwrap_err().code(), Code::DataLoss);
+
+            let got = frames(cfg, b"\x00\x00\x00\x05abc".to_vec()).await;
+            assert_eq!(got[0].as_ref().unwrap_err().code(), Code::DataLoss);
+
+            let limited = FrameConfig { max_len: 2, ..cfg };
+            let got = frames(limited, b"\x00\x00\x00\x03abc".to_vec()).await;
+            assert_eq!(got[0].as_ref().unwrap_err().code(), Code::ResourceExhausted);
+
+            let varint = FrameConfig::new(LengthPrefix::Varint);
+            let got = frames(varint, vec![0xff; 11]).await;
+            assert_eq!(got[0].as_ref().unwrap_err().code(), Code::DataLoss);
+        }
+    }
+}
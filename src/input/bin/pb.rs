@@ -0,0 +1,204 @@
+//! A [`BucketSource`] which decodes length-delimited protobuf messages
+//!
+//! Messages are prefixed with their lengths in varint(e.g, written by `writeDelimitedTo`).
+
+use core::marker::PhantomData;
+
+use futures::StreamExt;
+
+use tokio_stream::wrappers::ReceiverStream;
+
+use tonic::{Code, Status};
+
+use prost::Message;
+
+use crate::input::bin::framed::{framed_src_new, FrameConfig, LengthPrefix};
+use crate::input::conv::lines::ReadSource;
+use crate::input::source::BucketSource;
+use crate::item::item_status;
+
+fn decode<M>(ix: u64, raw: Vec<u8>) -> Result<(u64, M), Status>
+where
+    M: Message + Default,
+{
+    M::decode(raw.as_slice()).map(|m| (ix, m)).map_err(|e| {
+        let key: [u8; 8] = ix.to_be_bytes();
+        item_status(
+            Code::InvalidArgument,
+            format!("unable to decode a message #{ix}: {e}"),
+            &key,
+            raw,
+        )
+    })
+}
+
+pub struct PbSrc<B, M> {
+    framed: B,
+    msg: PhantomData<fn() -> M>,
+}
+
+#[tonic::async_trait]
+impl<B, M> BucketSource for PbSrc<B, M>
+where
+    B: BucketSource<K = u64, V = Vec<u8>>,
+    M: Message + Default + 'static,
+{
+    type Bucket = B::Bucket;
+    type K = u64;
+    type V = M;
+    type All = ReceiverStream<Result<(Self::K, Self::V), Status>>;
+
+    /// Gets all messages with their indices.
+    ///
+    /// A message which can not be decoded is reported as an item error(see [`item_status`])
+    /// with its index(big endian) and raw bytes; later messages are still read.
+    /// Stops reading when the stream is dropped.
+    async fn get_all_by_bucket(&self, b: Self::Bucket) -> Result<Self::All, Status> {
+        let frames: B::All = self.framed.get_all_by_bucket(b).await?;
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        tokio::spawn(async move {
+            let mut decoded = frames.map(|rslt| rslt.and_then(|(ix, raw)| decode(ix, raw)));
+            while let Some(item) = decoded.next().await {
+                if tx.send(item).await.is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+}
+
+/// Creates a [`BucketSource`] which decodes length-delimited messages from a [`ReadSource`].
+///
+/// ## Arguments
+/// - rsrc: A [`ReadSource`] which has length-delimited messages
+/// - max_len: Messages longer than this are rejected before being read
+pub fn pb_src_new<R, M>(
+    rsrc: R,
+    max_len: u64,
+) -> impl BucketSource<Bucket = R::Bucket, K = u64, V = M>
+where
+    R: ReadSource,
+    M: Message + Default + 'static,
+{
+    let cfg = FrameConfig {
+        prefix: LengthPrefix::Varint,
+        max_len,
+    };
+    PbSrc {
+        framed: framed_src_new(rsrc, cfg),
+        msg: PhantomData,
+    }
+}
+
+#[cfg(test)]
+mod test_pb {
+    mod pb_src_new {
+        use std::pin::Pin;
+        use std::sync::Arc;
+        use std::task::{Context, Poll};
+        use std::time::Duration;
+
+        use futures::StreamExt;
+
+        use tokio::io::{AsyncRead, ReadBuf};
+
+        use tonic::{Code, Status};
+
+        use prost::Message;
+
+        use crate::input::bin::framed::DEFAULT_MAX_FRAME_LEN;
+        use crate::input::bin::pb::pb_src_new;
+        use crate::input::conv::lines::ReadSource;
+        use crate::input::source::BucketSource;
+        use crate::output::dead::Rejected;
+        use crate::testing::mem_new;
+
+        #[derive(Clone, PartialEq, Message)]
+        struct Event {
+            #[prost(uint64, tag = "1")]
+            id: u64,
+
+            #[prost(string, tag = "2")]
+            name: String,
+        }
+
+        /// Endless empty frames; notifies when dropped
+        struct Zeros {
+            dropped: Arc<tokio::sync::Notify>,
+        }
+
+        impl AsyncRead for Zeros {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                _: &mut Context<'_>,
+                buf: &mut ReadBuf<'_>,
+            ) -> Poll<std::io::Result<()>> {
+                let zeros: Vec<u8> = vec![0; buf.remaining()];
+                buf.put_slice(&zeros);
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        impl Drop for Zeros {
+            fn drop(&mut self) {
+                self.dropped.notify_one();
+            }
+        }
+
+        struct Endless {
+            dropped: Arc<tokio::sync::Notify>,
+        }
+
+        #[tonic::async_trait]
+        impl ReadSource for Endless {
+            type Bucket = ();
+            type R = Zeros;
+
+            async fn get_src_read_by_bucket(&self, _: Self::Bucket) -> Result<Self::R, Status> {
+                Ok(Zeros {
+                    dropped: self.dropped.clone(),
+                })
+            }
+        }
+
+        #[tokio::test]
+        async fn dropped() {
+            let dropped = Arc::new(tokio::sync::Notify::new());
+            let rsrc = Endless {
+                dropped: dropped.clone(),
+            };
+            let src = pb_src_new::<_, Event>(rsrc, DEFAULT_MAX_FRAME_LEN);
+            let all = src.get_all_by_bucket(()).await.unwrap();
+            let got: Vec<_> = all.take(2).collect().await;
+            assert_eq!(got.len(), 2);
+
+            let waited = tokio::time::timeout(Duration::from_secs(5), dropped.notified()).await;
+            assert!(waited.is_ok(), "the file is still read");
+        }
+
+        #[tokio::test]
+        async fn events() {
+            let ev = |id: u64, name: &str| Event {
+                id,
+                name: name.into(),
+            };
+            let mut raw: Vec<u8> = ev(1, "a").encode_length_delimited_to_vec();
+            raw.extend(b"\x02\xff\xff");
+            raw.extend(ev(3, "c").encode_length_delimited_to_vec());
+
+            let src = pb_src_new::<_, Event>(mem_new(), DEFAULT_MAX_FRAME_LEN);
+            let all = src.get_all_by_bucket(raw).await.unwrap();
+            let got: Vec<Result<(u64, Event), Status>> = all.collect().await;
+            assert_eq!(got.len(), 3);
+            assert_eq!(got[0].as_ref().unwrap(), &(0, ev(1, "a")));
+            assert_eq!(got[2].as_ref().unwrap(), &(2, ev(3, "c")));
+
+            let e: Status = got.into_iter().nth(1).unwrap().unwrap_err();
+            assert_eq!(e.code(), Code::InvalidArgument);
+            let r = Rejected::from_status(vec![], e);
+            assert_eq!(r.key, 1u64.to_be_bytes());
+            assert_eq!(r.raw, b"\xff\xff");
+        }
+    }
+}
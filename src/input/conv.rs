@@ -2,5 +2,19 @@ pub mod source;
 
 pub mod lines;
 
+pub mod rooted;
+
 #[cfg(feature = "gzip_tokio_async")]
 pub mod gzip;
+
+#[cfg(feature = "zstd_tokio_async")]
+pub mod zstd;
+
+#[cfg(feature = "bzip2_tokio_async")]
+pub mod bzip2;
+
+#[cfg(feature = "xz_tokio_async")]
+pub mod xz;
+
+#[cfg(feature = "async_tokio")]
+pub mod auto;
@@ -0,0 +1,242 @@
+//! Detects the compression of a [`ReadSource`] by magic bytes
+
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use std::io::Cursor;
+
+use tokio::io::{AsyncRead, AsyncReadExt, BufReader, ReadBuf};
+
+#[cfg(feature = "bzip2_tokio_async")]
+use crate::input::conv::bzip2::bz2_tokio::bzip2_decoder;
+#[cfg(feature = "gzip_tokio_async")]
+use crate::input::conv::gzip::gz_tokio::gzip_decoder;
+#[cfg(feature = "xz_tokio_async")]
+use crate::input::conv::xz::xz_tokio::xz_decoder;
+#[cfg(feature = "zstd_tokio_async")]
+use crate::input::conv::zstd::zst_tokio::zstd_decoder;
+
+use tonic::Status;
+
+use crate::input::conv::lines::ReadSource;
+
+/// Max length of magic bytes
+const MAGIC_LEN: usize = 6;
+
+/// A compression format detected by magic bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// No known magic bytes(plain data)
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+
+    /// Compressed data which can not be decoded(e.g, lz4, zip)
+    Unsupported(&'static str),
+}
+
+/// Detects a compression format from the first bytes
+pub fn detect(prefix: &[u8]) -> Compression {
+    if let [b'B', b'Z', b'h', b'1'..=b'9', ..] = prefix {
+        // "BZh" and the block size(plain text may start with "BZh")
+        return Compression::Bzip2;
+    }
+    const KNOWN: &[(&[u8], Compression)] = &[
+        (&[0x1f, 0x8b], Compression::Gzip),
+        (&[0x28, 0xb5, 0x2f, 0xfd], Compression::Zstd),
+        (&[0xfd, b'7', b'z', b'X', b'Z', 0x00], Compression::Xz),
+        (&[0x04, 0x22, 0x4d, 0x18], Compression::Unsupported("lz4")),
+        (&[0x1f, 0x9d], Compression::Unsupported("compress")),
+        (b"LZIP", Compression::Unsupported("lzip")),
+        (b"PK\x03\x04", Compression::Unsupported("zip")),
+        (
+            &[b'7', b'z', 0xbc, 0xaf, 0x27, 0x1c],
+            Compression::Unsupported("7z"),
+        ),
+    ];
+    KNOWN
+        .iter()
+        .find(|pair| prefix.starts_with(pair.0))
+        .map(|pair| pair.1)
+        .unwrap_or(Compression::None)
+}
+
+type Peeked<R> = tokio::io::Chain<Cursor<Vec<u8>>, R>;
+
+/// A reader which decodes the detected compression
+pub enum Decoded<R> {
+    Plain(Peeked<R>),
+    #[cfg(feature = "gzip_tokio_async")]
+    Gzip(async_compression::tokio::bufread::GzipDecoder<BufReader<Peeked<R>>>),
+    #[cfg(feature = "zstd_tokio_async")]
+    Zstd(async_compression::tokio::bufread::ZstdDecoder<BufReader<Peeked<R>>>),
+    #[cfg(feature = "bzip2_tokio_async")]
+    Bzip2(async_compression::tokio::bufread::BzDecoder<BufReader<Peeked<R>>>),
+    #[cfg(feature = "xz_tokio_async")]
+    Xz(async_compression::tokio::bufread::XzDecoder<BufReader<Peeked<R>>>),
+}
+
+impl<R> AsyncRead for Decoded<R>
+where
+    R: AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Self::Plain(r) => Pin::new(r).poll_read(cx, buf),
+            #[cfg(feature = "gzip_tokio_async")]
+            Self::Gzip(r) => Pin::new(r).poll_read(cx, buf),
+            #[cfg(feature = "zstd_tokio_async")]
+            Self::Zstd(r) => Pin::new(r).poll_read(cx, buf),
+            #[cfg(feature = "bzip2_tokio_async")]
+            Self::Bzip2(r) => Pin::new(r).poll_read(cx, buf),
+            #[cfg(feature = "xz_tokio_async")]
+            Self::Xz(r) => Pin::new(r).poll_read(cx, buf),
+        }
+    }
+}
+
+fn unsupported(name: &str) -> Status {
+    Status::unimplemented(format!("unsupported compression: {name}"))
+}
+
+fn decoded<R>(c: Compression, p: Peeked<R>) -> Result<Decoded<R>, Status>
+where
+    R: AsyncRead + Unpin,
+{
+    match c {
+        Compression::None => Ok(Decoded::Plain(p)),
+        #[cfg(feature = "gzip_tokio_async")]
+        Compression::Gzip => Ok(Decoded::Gzip(gzip_decoder(p))),
+        #[cfg(feature = "zstd_tokio_async")]
+        Compression::Zstd => Ok(Decoded::Zstd(zstd_decoder(p))),
+        #[cfg(feature = "bzip2_tokio_async")]
+        Compression::Bzip2 => Ok(Decoded::Bzip2(bzip2_decoder(p))),
+        #[cfg(feature = "xz_tokio_async")]
+        Compression::Xz => Ok(Decoded::Xz(xz_decoder(p))),
+        Compression::Unsupported(name) => Err(unsupported(name)),
+        #[allow(unreachable_patterns)]
+        disabled => Err(Status::unimplemented(format!(
+            "compression not enabled: {disabled:?}"
+        ))),
+    }
+}
+
+pub struct AutoDecodedSrc<R> {
+    encoded: R,
+}
+
+#[tonic::async_trait]
+impl<R> ReadSource for AutoDecodedSrc<R>
+where
+    R: ReadSource,
+{
+    type Bucket = R::Bucket;
+    type R = Decoded<R::R>;
+
+    async fn get_src_read_by_bucket(&self, b: Self::Bucket) -> Result<Self::R, Status> {
+        let mut ar: R::R = self.encoded.get_src_read_by_bucket(b).await?;
+        let mut prefix: Vec<u8> = Vec::with_capacity(MAGIC_LEN);
+        (&mut ar)
+            .take(MAGIC_LEN as u64)
+            .read_to_end(&mut prefix)
+            .await
+            .map_err(|e| Status::internal(format!("unable to read magic bytes: {e}")))?;
+        let c: Compression = detect(&prefix);
+        let peeked: Peeked<R::R> = Cursor::new(prefix).chain(ar);
+        decoded(c, peeked)
+    }
+}
+
+/// Creates a [`ReadSource`] which decodes gzip/zstd/bzip2/xz or passes plain data as is.
+///
+/// Concatenated members are decoded as a single stream(like the decoders of each format).
+///
+/// Only formats enabled by features(e.g, zstd_tokio_async) can be decoded;
+/// other compressed data(e.g, lz4) will be rejected.
+pub fn read_src_auto_decoded_new<R>(encoded: R) -> impl ReadSource<Bucket = R::Bucket>
+where
+    R: ReadSource,
+{
+    AutoDecodedSrc { encoded }
+}
+
+#[cfg(test)]
+mod test_auto {
+    mod read_src_auto_decoded_new {
+        use tokio::io::AsyncReadExt;
+
+        use tonic::Status;
+
+        use crate::input::conv::auto::read_src_auto_decoded_new;
+        use crate::testing::{mem_new, read_all, MEMBERS};
+
+        async fn read(raw: Vec<u8>) -> Result<Vec<u8>, Status> {
+            read_all(&read_src_auto_decoded_new(mem_new()), raw).await
+        }
+
+        #[tokio::test]
+        async fn plain() {
+            assert_eq!(read(b"hi".to_vec()).await.unwrap(), b"hi".to_vec());
+            assert_eq!(read(vec![]).await.unwrap(), Vec::<u8>::new());
+        }
+
+        #[cfg(feature = "gzip_tokio_async")]
+        #[tokio::test]
+        async fn gzip() {
+            use async_compression::tokio::bufread::GzipEncoder;
+            let mut gz: Vec<u8> = vec![];
+            GzipEncoder::new(&b"line1\nline2\n"[..])
+                .read_to_end(&mut gz)
+                .await
+                .unwrap();
+            assert_eq!(read(gz).await.unwrap(), b"line1\nline2\n".to_vec());
+        }
+
+        #[cfg(feature = "gzip_tokio_async")]
+        #[tokio::test]
+        async fn gzip_members() {
+            let gz = include_bytes!("../../../testdata/multi-member.txt.gz");
+            assert_eq!(read(gz.to_vec()).await.unwrap(), MEMBERS.to_vec());
+        }
+
+        #[cfg(feature = "zstd_tokio_async")]
+        #[tokio::test]
+        async fn zstd_members() {
+            let zst = include_bytes!("../../../testdata/multi-member.txt.zst");
+            assert_eq!(read(zst.to_vec()).await.unwrap(), MEMBERS.to_vec());
+        }
+
+        #[cfg(feature = "bzip2_tokio_async")]
+        #[tokio::test]
+        async fn bzip2_members() {
+            let bz2 = include_bytes!("../../../testdata/multi-member.txt.bz2");
+            assert_eq!(read(bz2.to_vec()).await.unwrap(), MEMBERS.to_vec());
+        }
+
+        #[cfg(feature = "xz_tokio_async")]
+        #[tokio::test]
+        async fn xz_members() {
+            let xz = include_bytes!("../../../testdata/multi-member.txt.xz");
+            assert_eq!(read(xz.to_vec()).await.unwrap(), MEMBERS.to_vec());
+        }
+
+        #[tokio::test]
+        async fn bzh_text() {
+            let text: Vec<u8> = b"BZh is not bzip2\n".to_vec();
+            assert_eq!(read(text.clone()).await.unwrap(), text);
+        }
+
+        #[tokio::test]
+        async fn unsupported() {
+            let lz4: Vec<u8> = vec![0x04, 0x22, 0x4d, 0x18, 0x64, 0x40];
+            let e: Status = read(lz4).await.unwrap_err();
+            assert_eq!(e.code(), tonic::Code::Unimplemented);
+        }
+    }
+}
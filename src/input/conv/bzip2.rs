@@ -0,0 +1,2 @@
+#[cfg(feature = "bzip2_tokio_async")]
+pub mod bz2_tokio;
@@ -0,0 +1,60 @@
+use tokio::io::{AsyncRead, BufReader};
+
+use tonic::Status;
+
+use async_compression::tokio::bufread::BzDecoder;
+
+use crate::input::conv::lines::ReadSource;
+
+/// Creates a decoder which decodes concatenated streams as a single stream
+pub fn bzip2_decoder<R>(r: R) -> BzDecoder<BufReader<R>>
+where
+    R: AsyncRead,
+{
+    let mut bz = BzDecoder::new(BufReader::new(r));
+    bz.multiple_members(true);
+    bz
+}
+
+pub struct Bzip2DecodedSrc<R> {
+    encoded: R,
+}
+
+#[tonic::async_trait]
+impl<R> ReadSource for Bzip2DecodedSrc<R>
+where
+    R: ReadSource,
+{
+    type Bucket = R::Bucket;
+    type R = BzDecoder<BufReader<R::R>>;
+
+    async fn get_src_read_by_bucket(&self, b: Self::Bucket) -> Result<Self::R, Status> {
+        let ar: R::R = self.encoded.get_src_read_by_bucket(b).await?;
+        Ok(bzip2_decoder(ar))
+    }
+}
+
+/// Creates a [`ReadSource`] from bzip2-compressed [`ReadSource`]
+///
+/// Concatenated streams(e.g, `cat a.bz2 b.bz2`, pbzip2) are decoded as a single stream.
+pub fn read_src_bzip2_decoded_new<R>(encoded: R) -> impl ReadSource<Bucket = R::Bucket>
+where
+    R: ReadSource,
+{
+    Bzip2DecodedSrc { encoded }
+}
+
+#[cfg(test)]
+mod test_bz2_tokio {
+    mod read_src_bzip2_decoded_new {
+        use crate::input::conv::bzip2::bz2_tokio::read_src_bzip2_decoded_new;
+        use crate::testing::{mem_new, read_all, MEMBERS};
+
+        #[tokio::test]
+        async fn multi_member() {
+            let raw: &[u8] = include_bytes!("../../../../testdata/multi-member.txt.bz2");
+            let src = read_src_bzip2_decoded_new(mem_new());
+            assert_eq!(read_all(&src, raw).await.unwrap(), MEMBERS);
+        }
+    }
+}
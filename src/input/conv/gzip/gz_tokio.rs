@@ -1,4 +1,4 @@
-use tokio::io::BufReader;
+use tokio::io::{AsyncRead, BufReader};
 
 use tonic::Status;
 
@@ -6,6 +6,16 @@ use async_compression::tokio::bufread::GzipDecoder;
 
 use crate::input::conv::lines::ReadSource;
 
+/// Creates a decoder which decodes concatenated members as a single stream
+pub fn gzip_decoder<R>(r: R) -> GzipDecoder<BufReader<R>>
+where
+    R: AsyncRead,
+{
+    let mut gr = GzipDecoder::new(BufReader::new(r));
+    gr.multiple_members(true);
+    gr
+}
+
 pub struct GzipDecodedSrc<R> {
     encoded: R,
 }
@@ -20,16 +30,31 @@ where
 
     async fn get_src_read_by_bucket(&self, b: Self::Bucket) -> Result<Self::R, Status> {
         let ar: R::R = self.encoded.get_src_read_by_bucket(b).await?;
-        let br: BufReader<R::R> = BufReader::new(ar);
-        let gr: GzipDecoder<BufReader<_>> = GzipDecoder::new(br);
-        Ok(gr)
+        Ok(gzip_decoder(ar))
     }
 }
 
 /// Creates a [`ReadSource`] from gzipped [`ReadSource`]
+///
+/// Concatenated gzip members(e.g, `cat a.gz b.gz`) are decoded as a single stream.
 pub fn read_src_gzip_decoded_new<R>(encoded: R) -> impl ReadSource<Bucket = R::Bucket>
 where
     R: ReadSource,
 {
     GzipDecodedSrc { encoded }
 }
+
+#[cfg(test)]
+mod test_gz_tokio {
+    mod read_src_gzip_decoded_new {
+        use crate::input::conv::gzip::gz_tokio::read_src_gzip_decoded_new;
+        use crate::testing::{mem_new, read_all, MEMBERS};
+
+        #[tokio::test]
+        async fn multi_member() {
+            let raw: &[u8] = include_bytes!("../../../../testdata/multi-member.txt.gz");
+            let src = read_src_gzip_decoded_new(mem_new());
+            assert_eq!(read_all(&src, raw).await.unwrap(), MEMBERS);
+        }
+    }
+}
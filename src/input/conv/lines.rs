@@ -1,15 +1,18 @@
 use std::path::Path;
+use std::sync::Arc;
 
-use futures::StreamExt;
+use tokio::io::BufReader;
 
-use tokio::io::{AsyncBufReadExt, BufReader};
-
-use tokio_stream::wrappers::{ReceiverStream, SplitStream};
+use tokio_stream::wrappers::ReceiverStream;
 
 use tonic::Status;
 
+use crate::input::conv::lines::delim::{Delimiter, Next, Splitter};
 use crate::input::source::BucketSource;
 
+pub mod delim;
+pub mod offset;
+
 /// A trait which gets a readable object by bucket
 #[tonic::async_trait]
 pub trait ReadSource: Send + Sync + 'static {
@@ -21,6 +24,35 @@ pub trait ReadSource: Send + Sync + 'static {
 
 pub struct ReadSrc<R> {
     rsrc: R,
+    splitter: Arc<Splitter>,
+}
+
+async fn read_records<R>(
+    r: R,
+    splitter: Arc<Splitter>,
+    tx: tokio::sync::mpsc::Sender<Result<(usize, Vec<u8>), Status>>,
+) where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    let mut br = BufReader::new(r);
+    let mut offset: u64 = 0;
+    for ix in 0.. {
+        let mut buf: Vec<u8> = vec![];
+        let key: [u8; 8] = (ix as u64).to_be_bytes();
+        let (cnt, item) = match splitter.next(&mut br, &mut buf, offset, &key).await {
+            Next::End => return,
+            Next::Record(cnt) => (cnt, Ok((ix, buf))),
+            Next::Skip(cnt, e) => (cnt, Err(e)),
+            Next::Stop(e) => {
+                let _ = tx.send(Err(e)).await;
+                return;
+            }
+        };
+        if tx.send(item).await.is_err() {
+            return;
+        }
+        offset += cnt as u64;
+    }
 }
 
 #[tonic::async_trait]
@@ -35,37 +67,43 @@ where
 
     async fn get_all_by_bucket(&self, b: Self::Bucket) -> Result<Self::All, Status> {
         let r: R::R = self.rsrc.get_src_read_by_bucket(b).await?;
-        let br = BufReader::new(r);
-        let splited = br.split(b'\n');
-        let ss = SplitStream::new(splited);
         let (tx, rx) = tokio::sync::mpsc::channel(1);
-        tokio::spawn(async move {
-            let mapd = ss
-                .map(|rslt| {
-                    rslt.map_err(|e| Status::internal(format!("unable to get a line: {e}")))
-                })
-                .enumerate()
-                .map(|pair: (usize, _)| {
-                    let (ix, item) = pair;
-                    item.map(|v: Self::V| (ix, v))
-                });
-            let rt = &tx;
-            let _cnt: u64 = mapd
-                .fold(0, |tot, rslt| async move {
-                    rt.send(rslt).await.map(|_| 1 + tot).unwrap_or(tot)
-                })
-                .await;
-        });
+        tokio::spawn(read_records(r, self.splitter.clone(), tx));
         Ok(ReceiverStream::new(rx))
     }
 }
 
-/// Creates a [`BucketSource`] from [`ReadSource`]
+/// Creates a [`BucketSource`] from [`ReadSource`](records end with `b'\n'`)
 pub fn bytes_src_new<R>(rsrc: R) -> impl BucketSource<Bucket = R::Bucket, K = usize, V = Vec<u8>>
 where
     R: ReadSource,
 {
-    ReadSrc { rsrc }
+    bytes_src_new_with_delimiter(rsrc, Delimiter::default())
+}
+
+/// Creates a [`BucketSource`] from [`ReadSource`] which splits records by the [`Delimiter`]
+pub fn bytes_src_new_with_delimiter<R>(
+    rsrc: R,
+    delim: Delimiter,
+) -> impl BucketSource<Bucket = R::Bucket, K = usize, V = Vec<u8>>
+where
+    R: ReadSource,
+{
+    bytes_src_new_with_splitter(rsrc, delim.into())
+}
+
+/// Creates a [`BucketSource`] from [`ReadSource`] which splits records by the [`Splitter`]
+pub fn bytes_src_new_with_splitter<R>(
+    rsrc: R,
+    splitter: Splitter,
+) -> impl BucketSource<Bucket = R::Bucket, K = usize, V = Vec<u8>>
+where
+    R: ReadSource,
+{
+    ReadSrc {
+        rsrc,
+        splitter: Arc::new(splitter),
+    }
 }
 
 /// File Getter
@@ -0,0 +1,390 @@
+//! Record delimiters and length limits for line sources
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use tonic::{Code, Status};
+
+use crate::item::item_status;
+
+/// Record separator of JSON text sequences(RFC 7464)
+pub const RS: u8 = 0x1e;
+
+/// How to split records
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Delimiter {
+    /// Records end with the byte(e.g, `b'\n'`, `b'\0'` for `find -print0`)
+    Byte(u8),
+
+    /// Records end with the bytes
+    Bytes(Vec<u8>),
+
+    /// Records end with LF; a CR just before the LF is also removed
+    Crlf,
+
+    /// JSON text sequences(RFC 7464): records start with RS; a trailing LF is removed
+    JsonSeq,
+}
+
+impl Default for Delimiter {
+    /// Records end with `b'\n'`
+    fn default() -> Self {
+        Self::Byte(b'\n')
+    }
+}
+
+impl Delimiter {
+    /// Creates a multi-byte delimiter(fails if empty)
+    pub fn bytes(d: Vec<u8>) -> Result<Self, Status> {
+        match d.len() {
+            0 => Err(Status::invalid_argument("empty delimiter")),
+            1 => Ok(Self::Byte(d[0])),
+            _ => Ok(Self::Bytes(d)),
+        }
+    }
+
+    /// The last byte of the delimiter
+    fn end(&self) -> u8 {
+        match self {
+            Self::Byte(b) => *b,
+            Self::Bytes(d) => d.last().copied().unwrap_or(b'\n'),
+            Self::Crlf => b'\n',
+            Self::JsonSeq => RS,
+        }
+    }
+
+    /// The max number of bytes removed by [`Self::strip`]
+    fn stripped_len(&self) -> usize {
+        match self {
+            Self::Byte(_) => 1,
+            Self::Bytes(d) => d.len(),
+            Self::Crlf => 2,
+            Self::JsonSeq => 2,
+        }
+    }
+
+    /// Reads bytes until the end of the delimiter(or EOF).
+    ///
+    /// Bytes after the first `cap` bytes are discarded(except the delimiter).
+    /// Returns the number of bytes consumed and true if some bytes were discarded.
+    async fn read_piece<R>(
+        &self,
+        r: &mut R,
+        buf: &mut Vec<u8>,
+        cap: usize,
+    ) -> std::io::Result<(usize, bool)>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        let end: u8 = self.end();
+        let keep: usize = self.stripped_len();
+        let mut tot: usize = 0;
+        let mut discarded: bool = false;
+        loop {
+            let avail: &[u8] = r.fill_buf().await?;
+            if avail.is_empty() {
+                return Ok((tot, discarded));
+            }
+            let found: Option<usize> = avail.iter().position(|b| *b == end);
+            let take: usize = found.map(|ix| ix + 1).unwrap_or(avail.len());
+            let chunk: &[u8] = &avail[..take];
+            let room: usize = cap.saturating_add(keep).saturating_sub(buf.len());
+            let (head, tail) = chunk.split_at(chunk.len().min(room));
+            buf.extend_from_slice(head);
+            buf.extend_from_slice(&tail[tail.len().saturating_sub(keep)..]);
+            r.consume(take);
+            tot += take;
+            if buf.len() > cap.saturating_add(keep) {
+                buf.drain(cap..buf.len() - keep);
+                discarded = true;
+            }
+            let ended: bool = match self {
+                Self::Bytes(d) => buf.ends_with(d),
+                _ => true,
+            };
+            if found.is_some() && ended {
+                return Ok((tot, discarded));
+            }
+        }
+    }
+
+    fn strip(&self, buf: &mut Vec<u8>) {
+        let mut pop = |b: u8| {
+            if buf.last() == Some(&b) {
+                buf.pop();
+                true
+            } else {
+                false
+            }
+        };
+        match self {
+            Self::Byte(b) => {
+                pop(*b);
+            }
+            Self::Crlf => {
+                if pop(b'\n') {
+                    pop(b'\r');
+                }
+            }
+            Self::JsonSeq => {
+                pop(RS);
+                pop(b'\n');
+            }
+            Self::Bytes(d) => {
+                if buf.ends_with(d) {
+                    buf.truncate(buf.len() - d.len());
+                }
+            }
+        }
+    }
+
+    /// Reads a record(without the delimiter) into the buffer.
+    ///
+    /// Returns the number of bytes consumed(None: no more record).
+    /// Empty JSON text sequence records are skipped.
+    pub async fn read_record<R>(
+        &self,
+        r: &mut R,
+        buf: &mut Vec<u8>,
+    ) -> std::io::Result<Option<usize>>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        let rec: Option<Record> = self.read_record_max(r, buf, usize::MAX).await?;
+        Ok(rec.map(|r| r.consumed))
+    }
+
+    /// Reads a record(without the delimiter) into the buffer keeping at most `max` bytes.
+    ///
+    /// Bytes of a longer record are discarded while reading(never buffered).
+    pub async fn read_record_max<R>(
+        &self,
+        r: &mut R,
+        buf: &mut Vec<u8>,
+        max: usize,
+    ) -> std::io::Result<Option<Record>>
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        let mut consumed: usize = 0;
+        loop {
+            buf.clear();
+            let (cnt, discarded) = self.read_piece(r, buf, max).await?;
+            if 0 == cnt {
+                return Ok(None);
+            }
+            consumed += cnt;
+            self.strip(buf);
+            let truncated: bool = discarded || max < buf.len();
+            buf.truncate(max);
+            if !buf.is_empty() || Self::JsonSeq != *self {
+                return Ok(Some(Record {
+                    consumed,
+                    truncated,
+                }));
+            }
+        }
+    }
+}
+
+/// A record read by [`Delimiter::read_record_max`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record {
+    /// The number of bytes consumed(including the delimiter)
+    pub consumed: usize,
+
+    /// True if the record was longer than the max
+    pub truncated: bool,
+}
+
+/// What to do with a record longer than the max
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TooLong {
+    /// Keeps the first bytes of the record
+    Truncate,
+
+    /// Skips the record and reports it as an item error(e.g, for a dead letter)
+    Reject,
+
+    /// Stops reading with [`Code::ResourceExhausted`]
+    Fail,
+}
+
+/// Maximum length of a record(without the delimiter)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MaxLen {
+    pub max: usize,
+    pub too_long: TooLong,
+}
+
+/// The next item read by [`Splitter::next`]
+pub enum Next {
+    /// No more records
+    End,
+
+    /// A record(in the buffer) and the number of bytes consumed
+    Record(usize),
+
+    /// A rejected record and the number of bytes consumed
+    Skip(usize, Status),
+
+    /// An error which stops reading
+    Stop(Status),
+}
+
+/// Splits records by a [`Delimiter`] with an optional [`MaxLen`]
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Splitter {
+    pub delim: Delimiter,
+    pub max_len: Option<MaxLen>,
+}
+
+impl From<Delimiter> for Splitter {
+    fn from(delim: Delimiter) -> Self {
+        Self {
+            delim,
+            max_len: None,
+        }
+    }
+}
+
+impl Splitter {
+    /// Reads the next record at the offset.
+    ///
+    /// The key is kept in a rejected item error(see [`item_status`]).
+    pub async fn next<R>(&self, r: &mut R, buf: &mut Vec<u8>, offset: u64, key: &[u8]) -> Next
+    where
+        R: AsyncBufRead + Unpin,
+    {
+        let max: usize = self.max_len.map(|m| m.max).unwrap_or(usize::MAX);
+        let rec: Record = match self.delim.read_record_max(r, buf, max).await {
+            Ok(None) => return Next::End,
+            Ok(Some(rec)) => rec,
+            Err(e) => {
+                return Next::Stop(Status::internal(format!(
+                    "unable to get a line at {offset}: {e}"
+                )))
+            }
+        };
+        let too_long: TooLong = match (rec.truncated, self.max_len) {
+            (true, Some(m)) => m.too_long,
+            _ => return Next::Record(rec.consumed),
+        };
+        let msg: String = format!("a record at {offset} longer than {max} bytes");
+        match too_long {
+            TooLong::Truncate => Next::Record(rec.consumed),
+            TooLong::Reject => Next::Skip(
+                rec.consumed,
+                item_status(Code::ResourceExhausted, msg, key, core::mem::take(buf)),
+            ),
+            TooLong::Fail => Next::Stop(Status::resource_exhausted(msg)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_delim {
+    mod delimiter {
+        use crate::input::conv::lines::delim::Delimiter;
+
+        async fn split(d: Delimiter, input: &[u8]) -> Vec<(usize, Vec<u8>)> {
+            let mut r = input;
+            let mut buf: Vec<u8> = vec![];
+            let mut got = vec![];
+            while let Some(cnt) = d.read_record(&mut r, &mut buf).await.unwrap() {
+                got.push((cnt, buf.clone()));
+            }
+            got
+        }
+
+        fn pairs(v: &[(usize, &str)]) -> Vec<(usize, Vec<u8>)> {
+            v.iter().map(|p| (p.0, p.1.as_bytes().to_vec())).collect()
+        }
+
+        #[tokio::test]
+        async fn delimiters() {
+            let lf = split(Delimiter::default(), b"a\r\n\nb").await;
+            assert_eq!(lf, pairs(&[(3, "a\r"), (1, ""), (1, "b")]));
+
+            let crlf = split(Delimiter::Crlf, b"a\r\nb\nc\r").await;
+            assert_eq!(crlf, pairs(&[(3, "a"), (2, "b"), (2, "c\r")]));
+
+            let nul = split(Delimiter::Byte(0), b"x/y\0z\0").await;
+            assert_eq!(nul, pairs(&[(4, "x/y"), (2, "z")]));
+
+            let multi = split(Delimiter::bytes(b"--".to_vec()).unwrap(), b"a-b--c--").await;
+            assert_eq!(multi, pairs(&[(5, "a-b"), (3, "c")]));
+
+            let seq = split(Delimiter::JsonSeq, b"\x1e{\"a\":1}\n\x1e\x1e2\n").await;
+            assert_eq!(seq, pairs(&[(10, "{\"a\":1}"), (3, "2")]));
+
+            assert!(Delimiter::bytes(vec![]).is_err());
+            assert_eq!(Delimiter::bytes(vec![0]).unwrap(), Delimiter::Byte(0));
+        }
+    }
+
+    mod splitter {
+        use tonic::Code;
+
+        use crate::input::conv::lines::delim::{Delimiter, MaxLen, Next, Splitter, TooLong};
+        use crate::output::dead::Rejected;
+
+        fn input() -> Vec<u8> {
+            let mut v: Vec<u8> = b"ab\r\n".to_vec();
+            v.extend(vec![b'x'; 100_000]);
+            v.extend(b"\r\ncd\r\n");
+            v
+        }
+
+        fn splitter(too_long: TooLong) -> Splitter {
+            Splitter {
+                delim: Delimiter::Crlf,
+                max_len: Some(MaxLen { max: 3, too_long }),
+            }
+        }
+
+        async fn next(s: &Splitter, r: &mut &[u8], offset: u64) -> (Next, Vec<u8>) {
+            let mut buf: Vec<u8> = Vec::with_capacity(0);
+            let n: Next = s.next(r, &mut buf, offset, &offset.to_be_bytes()).await;
+            assert!(buf.capacity() < 10_000);
+            (n, buf)
+        }
+
+        #[tokio::test]
+        async fn too_long() {
+            let v: Vec<u8> = input();
+
+            let s: Splitter = splitter(TooLong::Truncate);
+            let mut r: &[u8] = &v;
+            assert!(matches!(next(&s, &mut r, 0).await, (Next::Record(4), b) if b == b"ab"));
+            assert!(matches!(next(&s, &mut r, 4).await, (Next::Record(100_002), b) if b == b"xxx"));
+            assert!(matches!(next(&s, &mut r, 100_006).await, (Next::Record(4), b) if b == b"cd"));
+            assert!(matches!(next(&s, &mut r, 100_010).await, (Next::End, _)));
+
+            let s: Splitter = splitter(TooLong::Reject);
+            let mut r: &[u8] = &v;
+            next(&s, &mut r, 0).await;
+            let rejected: Rejected = match next(&s, &mut r, 4).await {
+                (Next::Skip(100_002, e), _) => Rejected::from_status(vec![], e),
+                _ => panic!("must be skipped"),
+            };
+            assert_eq!(rejected.status.code(), Code::ResourceExhausted);
+            assert_eq!(rejected.key, 4u64.to_be_bytes());
+            assert_eq!(rejected.raw, b"xxx");
+            assert!(matches!(
+                next(&s, &mut r, 100_006).await,
+                (Next::Record(4), _)
+            ));
+
+            let s: Splitter = splitter(TooLong::Fail);
+            let mut r: &[u8] = &v;
+            next(&s, &mut r, 0).await;
+            match next(&s, &mut r, 4).await {
+                (Next::Stop(e), _) => {
+                    assert_eq!(e.code(), Code::ResourceExhausted);
+                    assert!(e.message().contains(" 4 "));
+                }
+                _ => panic!("must stop"),
+            }
+        }
+    }
+}
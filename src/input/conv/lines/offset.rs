@@ -0,0 +1,198 @@
+//! Lines keyed by byte offsets(to locate a record or to resume mid-file)
+
+use std::io::SeekFrom;
+use std::sync::Arc;
+
+use tokio::io::{AsyncSeekExt, BufReader};
+
+use tokio_stream::wrappers::ReceiverStream;
+
+use tonic::Status;
+
+use crate::input::conv::lines::delim::{Delimiter, Next, Splitter};
+use crate::input::conv::lines::{FsSource, ReadSource};
+use crate::input::source::BucketSource;
+
+/// A [`ReadSource`] which can start reading at a byte offset
+#[tonic::async_trait]
+pub trait SeekReadSource: ReadSource {
+    /// Gets a readable object positioned at the offset
+    async fn get_src_read_at(&self, b: Self::Bucket, offset: u64) -> Result<Self::R, Status>;
+}
+
+#[tonic::async_trait]
+impl<F> SeekReadSource for F
+where
+    F: FsSource,
+{
+    async fn get_src_read_at(&self, b: Self::Bucket, offset: u64) -> Result<Self::R, Status> {
+        let mut f: tokio::fs::File = self.get_file_by_bucket(b).await?;
+        f.seek(SeekFrom::Start(offset))
+            .await
+            .map_err(|e| Status::internal(format!("unable to seek to {offset}: {e}")))?;
+        Ok(f)
+    }
+}
+
+/// The position of a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct LinePos {
+    /// The byte offset of the first byte of the line
+    pub offset: u64,
+
+    /// The line number(0-based; relative to the line number of the start position)
+    pub line: u64,
+}
+
+/// Where to start reading lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Start {
+    /// Reads all lines
+    Begin,
+
+    /// Reads lines from the position(the offset must be the start of a line)
+    At(LinePos),
+
+    /// Reads lines after the line at the position(e.g, the last key acknowledged)
+    After(LinePos),
+}
+
+async fn read_lines<R>(
+    r: R,
+    start: Start,
+    splitter: Arc<Splitter>,
+    tx: tokio::sync::mpsc::Sender<Result<(LinePos, Vec<u8>), Status>>,
+) where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    let mut br = BufReader::new(r);
+    let (mut pos, mut skip) = match start {
+        Start::Begin => (LinePos::default(), false),
+        Start::At(p) => (p, false),
+        Start::After(p) => (p, true),
+    };
+    loop {
+        let mut buf: Vec<u8> = vec![];
+        let key: [u8; 8] = pos.offset.to_be_bytes();
+        let (cnt, item) = match splitter.next(&mut br, &mut buf, pos.offset, &key).await {
+            Next::End => return,
+            Next::Record(cnt) => (cnt, Ok((pos, buf))),
+            Next::Skip(cnt, e) => (cnt, Err(e)),
+            Next::Stop(e) => {
+                let _ = tx.send(Err(e)).await;
+                return;
+            }
+        };
+        if !skip && tx.send(item).await.is_err() {
+            return;
+        }
+        skip = false;
+        pos.offset += cnt as u64;
+        pos.line += 1;
+    }
+}
+
+pub struct OffsetSrc<R> {
+    rsrc: R,
+    splitter: Arc<Splitter>,
+}
+
+#[tonic::async_trait]
+impl<R> BucketSource for OffsetSrc<R>
+where
+    R: SeekReadSource,
+{
+    type Bucket = (R::Bucket, Start);
+    type K = LinePos;
+    type V = Vec<u8>;
+    type All = ReceiverStream<Result<(Self::K, Self::V), Status>>;
+
+    /// Gets lines(without the delimiter) from the start position
+    async fn get_all_by_bucket(&self, b: Self::Bucket) -> Result<Self::All, Status> {
+        let (bkt, start) = b;
+        let offset: u64 = match start {
+            Start::Begin => 0,
+            Start::At(p) | Start::After(p) => p.offset,
+        };
+        let r: R::R = self.rsrc.get_src_read_at(bkt, offset).await?;
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        tokio::spawn(read_lines(r, start, self.splitter.clone(), tx));
+        Ok(ReceiverStream::new(rx))
+    }
+}
+
+/// Creates a [`BucketSource`] which keys lines by their positions(lines end with `b'\n'`)
+pub fn offset_src_new<R>(
+    rsrc: R,
+) -> impl BucketSource<Bucket = (R::Bucket, Start), K = LinePos, V = Vec<u8>>
+where
+    R: SeekReadSource,
+{
+    offset_src_new_with_delimiter(rsrc, Delimiter::default())
+}
+
+/// Creates a [`BucketSource`] which keys records split by the [`Delimiter`] by their positions
+pub fn offset_src_new_with_delimiter<R>(
+    rsrc: R,
+    delim: Delimiter,
+) -> impl BucketSource<Bucket = (R::Bucket, Start), K = LinePos, V = Vec<u8>>
+where
+    R: SeekReadSource,
+{
+    offset_src_new_with_splitter(rsrc, delim.into())
+}
+
+/// Creates a [`BucketSource`] which keys records split by the [`Splitter`] by their positions
+pub fn offset_src_new_with_splitter<R>(
+    rsrc: R,
+    splitter: Splitter,
+) -> impl BucketSource<Bucket = (R::Bucket, Start), K = LinePos, V = Vec<u8>>
+where
+    R: SeekReadSource,
+{
+    OffsetSrc {
+        rsrc,
+        splitter: Arc::new(splitter),
+    }
+}
+
+#[cfg(test)]
+mod test_offset {
+    mod offset_src_new {
+        use futures::StreamExt;
+
+        use crate::input::conv::lines::offset::{offset_src_new, LinePos, Start};
+        use crate::input::source::BucketSource;
+        use crate::testing::TempDir;
+
+        #[tokio::test]
+        async fn resume() {
+            let dir = TempDir::new("offset");
+            std::fs::write(dir.join("l.txt"), b"ab\n\ncde\nf").unwrap();
+            let src = offset_src_new(dir.dir());
+
+            let all = src.get_all_by_bucket(("l.txt", Start::Begin)).await;
+            let got: Vec<(LinePos, Vec<u8>)> = all.unwrap().map(|r| r.unwrap()).collect().await;
+            let pos = |offset: u64, line: u64| LinePos { offset, line };
+            assert_eq!(
+                got,
+                vec![
+                    (pos(0, 0), b"ab".to_vec()),
+                    (pos(3, 1), vec![]),
+                    (pos(4, 2), b"cde".to_vec()),
+                    (pos(8, 3), b"f".to_vec()),
+                ]
+            );
+
+            let all = src
+                .get_all_by_bucket(("l.txt", Start::After(got[2].0)))
+                .await;
+            let rest: Vec<(LinePos, Vec<u8>)> = all.unwrap().map(|r| r.unwrap()).collect().await;
+            assert_eq!(rest, got[3..].to_vec());
+
+            let all = src.get_all_by_bucket(("l.txt", Start::At(got[1].0))).await;
+            let rest: Vec<(LinePos, Vec<u8>)> = all.unwrap().map(|r| r.unwrap()).collect().await;
+            assert_eq!(rest, got[1..].to_vec());
+        }
+    }
+}
@@ -0,0 +1,195 @@
+//! A [`FsSource`] which never resolves a bucket to a path outside its root
+
+use std::io::ErrorKind;
+use std::marker::PhantomData;
+use std::path::{Component, Path, PathBuf};
+
+use tonic::Status;
+
+use crate::input::conv::lines::FsSource;
+
+/// How to resolve symbolic links under the root(see [`crate::input::walk::SymlinkPolicy`] for walking)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RootedSymlinks {
+    /// Rejects a path if any of its components is a symbolic link
+    Deny,
+
+    /// Follows symbolic links if the resolved path is still under the root
+    #[default]
+    WithinRoot,
+
+    /// Follows symbolic links even if they point outside of the root
+    Anywhere,
+}
+
+fn io2status(e: std::io::Error, p: &Path) -> Status {
+    match e.kind() {
+        ErrorKind::NotFound => Status::not_found(format!("no such file: {}", p.display())),
+        _ => Status::internal(format!("unable to resolve {}: {e}", p.display())),
+    }
+}
+
+/// Checks a relative path lexically(no NUL bytes, no root, no parent dir)
+pub fn check_relative(rel: &Path) -> Result<(), Status> {
+    if rel.as_os_str().as_encoded_bytes().contains(&0) {
+        return Err(Status::invalid_argument("a path must not contain NUL"));
+    }
+    if rel.as_os_str().is_empty() {
+        return Err(Status::invalid_argument("empty path"));
+    }
+    rel.components().try_for_each(|c| match c {
+        Component::Normal(_) | Component::CurDir => Ok(()),
+        Component::ParentDir => Err(Status::invalid_argument(format!(
+            "parent dir not allowed: {}",
+            rel.display()
+        ))),
+        Component::RootDir | Component::Prefix(_) => Err(Status::invalid_argument(format!(
+            "absolute path not allowed: {}",
+            rel.display()
+        ))),
+    })
+}
+
+pub struct RootedFs<B> {
+    root: PathBuf,
+    symlinks: RootedSymlinks,
+    bucket: PhantomData<B>,
+}
+
+impl<B> RootedFs<B> {
+    fn deny_symlinks(&self, rel: &Path) -> Result<(), Status> {
+        let mut p: PathBuf = self.root.clone();
+        for c in rel.components() {
+            p.push(c);
+            let meta = std::fs::symlink_metadata(&p).map_err(|e| io2status(e, &p))?;
+            if meta.file_type().is_symlink() {
+                return Err(Status::permission_denied(format!(
+                    "symbolic link not allowed: {}",
+                    p.display()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn within_root(&self, joined: &Path) -> Result<PathBuf, Status> {
+        let resolved: PathBuf = joined.canonicalize().map_err(|e| io2status(e, joined))?;
+        match resolved.starts_with(&self.root) {
+            true => Ok(resolved),
+            false => Err(Status::permission_denied(format!(
+                "path outside of the root: {}",
+                joined.display()
+            ))),
+        }
+    }
+
+    /// Resolves a relative path under the root
+    pub fn resolve(&self, rel: &Path) -> Result<PathBuf, Status> {
+        check_relative(rel)?;
+        let joined: PathBuf = self.root.join(rel);
+        match self.symlinks {
+            RootedSymlinks::Deny => self.deny_symlinks(rel).map(|_| joined),
+            RootedSymlinks::WithinRoot => self.within_root(&joined),
+            RootedSymlinks::Anywhere => Ok(joined),
+        }
+    }
+}
+
+impl<B> FsSource for RootedFs<B>
+where
+    B: AsRef<Path> + Send + Sync + 'static,
+{
+    type Bucket = B;
+    type P = PathBuf;
+
+    fn bucket2path(&self, b: Self::Bucket) -> Result<Self::P, Status> {
+        self.resolve(b.as_ref())
+    }
+}
+
+/// Creates a [`RootedFs`] which resolves buckets(relative paths) under the root.
+///
+/// The root is canonicalized here; fails if it does not exist.
+pub fn rooted_fs_new<B, P>(root: P, symlinks: RootedSymlinks) -> Result<RootedFs<B>, Status>
+where
+    P: AsRef<Path>,
+{
+    let root: &Path = root.as_ref();
+    let canonical: PathBuf = root.canonicalize().map_err(|e| io2status(e, root))?;
+    Ok(RootedFs {
+        root: canonical,
+        symlinks,
+        bucket: PhantomData,
+    })
+}
+
+#[cfg(test)]
+mod test_rooted {
+    mod rooted_fs_new {
+        use std::path::PathBuf;
+
+        use tonic::Code;
+
+        use crate::input::conv::lines::FsSource;
+        use crate::input::conv::rooted::{rooted_fs_new, RootedFs, RootedSymlinks};
+        use crate::testing::TempDir;
+
+        fn tmp(name: &str) -> TempDir {
+            let dir = TempDir::new(&format!("rooted-{name}"));
+            std::fs::create_dir_all(dir.join("root/sub")).unwrap();
+            std::fs::write(dir.join("root/sub/in.txt"), b"in").unwrap();
+            std::fs::write(dir.join("secret.txt"), b"secret").unwrap();
+            dir
+        }
+
+        fn code(fs: &RootedFs<String>, b: &str) -> Code {
+            fs.bucket2path(b.into()).unwrap_err().code()
+        }
+
+        #[test]
+        fn lexical() {
+            let dir: TempDir = tmp("lexical");
+            let fs: RootedFs<String> =
+                rooted_fs_new(dir.join("root"), RootedSymlinks::default()).unwrap();
+
+            let p: PathBuf = fs.bucket2path("sub/./in.txt".into()).unwrap();
+            assert_eq!(std::fs::read(p).unwrap(), b"in");
+
+            assert_eq!(code(&fs, "../secret.txt"), Code::InvalidArgument);
+            assert_eq!(code(&fs, "sub/../../secret.txt"), Code::InvalidArgument);
+            let abs: String = dir.join("secret.txt").display().to_string();
+            assert_eq!(code(&fs, &abs), Code::InvalidArgument);
+            assert_eq!(code(&fs, "sub/in.txt\0.png"), Code::InvalidArgument);
+            assert_eq!(code(&fs, ""), Code::InvalidArgument);
+            assert_eq!(code(&fs, "sub/none.txt"), Code::NotFound);
+
+            assert!(rooted_fs_new::<String, _>(dir.join("none"), RootedSymlinks::Deny).is_err());
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn symlinks() {
+            let dir: TempDir = tmp("symlinks");
+            let root: PathBuf = dir.join("root");
+            std::os::unix::fs::symlink(dir.join("secret.txt"), root.join("out")).unwrap();
+            std::os::unix::fs::symlink(root.join("sub/in.txt"), root.join("in")).unwrap();
+            std::os::unix::fs::symlink(dir.path(), root.join("sub/up")).unwrap();
+
+            let within: RootedFs<String> =
+                rooted_fs_new(&root, RootedSymlinks::WithinRoot).unwrap();
+            assert!(within.bucket2path("in".into()).is_ok());
+            assert_eq!(code(&within, "out"), Code::PermissionDenied);
+            assert_eq!(code(&within, "sub/up/secret.txt"), Code::PermissionDenied);
+
+            let deny: RootedFs<String> = rooted_fs_new(&root, RootedSymlinks::Deny).unwrap();
+            assert!(deny.bucket2path("sub/in.txt".into()).is_ok());
+            assert_eq!(code(&deny, "in"), Code::PermissionDenied);
+            assert_eq!(code(&deny, "sub/up/secret.txt"), Code::PermissionDenied);
+
+            let follow: RootedFs<String> = rooted_fs_new(&root, RootedSymlinks::Anywhere).unwrap();
+            let p: PathBuf = follow.bucket2path("out".into()).unwrap();
+            assert_eq!(std::fs::read(p).unwrap(), b"secret");
+            assert_eq!(code(&follow, "../secret.txt"), Code::InvalidArgument);
+        }
+    }
+}
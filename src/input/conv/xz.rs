@@ -0,0 +1,2 @@
+#[cfg(feature = "xz_tokio_async")]
+pub mod xz_tokio;
@@ -0,0 +1,60 @@
+use tokio::io::{AsyncRead, BufReader};
+
+use tonic::Status;
+
+use async_compression::tokio::bufread::XzDecoder;
+
+use crate::input::conv::lines::ReadSource;
+
+/// Creates a decoder which decodes concatenated streams as a single stream
+pub fn xz_decoder<R>(r: R) -> XzDecoder<BufReader<R>>
+where
+    R: AsyncRead,
+{
+    let mut xr = XzDecoder::new(BufReader::new(r));
+    xr.multiple_members(true);
+    xr
+}
+
+pub struct XzDecodedSrc<R> {
+    encoded: R,
+}
+
+#[tonic::async_trait]
+impl<R> ReadSource for XzDecodedSrc<R>
+where
+    R: ReadSource,
+{
+    type Bucket = R::Bucket;
+    type R = XzDecoder<BufReader<R::R>>;
+
+    async fn get_src_read_by_bucket(&self, b: Self::Bucket) -> Result<Self::R, Status> {
+        let ar: R::R = self.encoded.get_src_read_by_bucket(b).await?;
+        Ok(xz_decoder(ar))
+    }
+}
+
+/// Creates a [`ReadSource`] from xz-compressed [`ReadSource`]
+///
+/// Concatenated streams(e.g, `cat a.xz b.xz`) are decoded as a single stream.
+pub fn read_src_xz_decoded_new<R>(encoded: R) -> impl ReadSource<Bucket = R::Bucket>
+where
+    R: ReadSource,
+{
+    XzDecodedSrc { encoded }
+}
+
+#[cfg(test)]
+mod test_xz_tokio {
+    mod read_src_xz_decoded_new {
+        use crate::input::conv::xz::xz_tokio::read_src_xz_decoded_new;
+        use crate::testing::{mem_new, read_all, MEMBERS};
+
+        #[tokio::test]
+        async fn multi_member() {
+            let raw: &[u8] = include_bytes!("../../../../testdata/multi-member.txt.xz");
+            let src = read_src_xz_decoded_new(mem_new());
+            assert_eq!(read_all(&src, raw).await.unwrap(), MEMBERS);
+        }
+    }
+}
@@ -0,0 +1,2 @@
+#[cfg(feature = "zstd_tokio_async")]
+pub mod zst_tokio;
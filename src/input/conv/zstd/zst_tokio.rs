@@ -0,0 +1,60 @@
+use tokio::io::{AsyncRead, BufReader};
+
+use tonic::Status;
+
+use async_compression::tokio::bufread::ZstdDecoder;
+
+use crate::input::conv::lines::ReadSource;
+
+/// Creates a decoder which decodes concatenated frames as a single stream
+pub fn zstd_decoder<R>(r: R) -> ZstdDecoder<BufReader<R>>
+where
+    R: AsyncRead,
+{
+    let mut zr = ZstdDecoder::new(BufReader::new(r));
+    zr.multiple_members(true);
+    zr
+}
+
+pub struct ZstdDecodedSrc<R> {
+    encoded: R,
+}
+
+#[tonic::async_trait]
+impl<R> ReadSource for ZstdDecodedSrc<R>
+where
+    R: ReadSource,
+{
+    type Bucket = R::Bucket;
+    type R = ZstdDecoder<BufReader<R::R>>;
+
+    async fn get_src_read_by_bucket(&self, b: Self::Bucket) -> Result<Self::R, Status> {
+        let ar: R::R = self.encoded.get_src_read_by_bucket(b).await?;
+        Ok(zstd_decoder(ar))
+    }
+}
+
+/// Creates a [`ReadSource`] from zstd-compressed [`ReadSource`]
+///
+/// Concatenated frames(e.g, `cat a.zst b.zst`) are decoded as a single stream.
+pub fn read_src_zstd_decoded_new<R>(encoded: R) -> impl ReadSource<Bucket = R::Bucket>
+where
+    R: ReadSource,
+{
+    ZstdDecodedSrc { encoded }
+}
+
+#[cfg(test)]
+mod test_zst_tokio {
+    mod read_src_zstd_decoded_new {
+        use crate::input::conv::zstd::zst_tokio::read_src_zstd_decoded_new;
+        use crate::testing::{mem_new, read_all, MEMBERS};
+
+        #[tokio::test]
+        async fn multi_member() {
+            let raw: &[u8] = include_bytes!("../../../../testdata/multi-member.txt.zst");
+            let src = read_src_zstd_decoded_new(mem_new());
+            assert_eq!(read_all(&src, raw).await.unwrap(), MEMBERS);
+        }
+    }
+}
@@ -0,0 +1,311 @@
+//! Delimited text(CSV/TSV) sources which deserialize records
+//!
+//! Records are parsed by the csv crate on a blocking thread;
+//! quoted fields may contain delimiters and newlines.
+
+use core::marker::PhantomData;
+
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::io::SyncIoBridge;
+
+use tonic::Status;
+
+use csv::{ReaderBuilder, StringRecord};
+
+use crate::input::conv::lines::ReadSource;
+use crate::input::source::BucketSource;
+
+/// Dialect of delimited text
+#[derive(Debug, Clone, Copy)]
+pub struct DelimitedConfig {
+    pub delimiter: u8,
+    pub quote: u8,
+
+    /// The first record is a header(column names)
+    pub has_headers: bool,
+
+    /// Allows records with different number of fields
+    pub flexible: bool,
+}
+
+impl Default for DelimitedConfig {
+    fn default() -> Self {
+        Self::csv()
+    }
+}
+
+impl DelimitedConfig {
+    /// Comma separated values with a header
+    pub fn csv() -> Self {
+        Self {
+            delimiter: b',',
+            quote: b'"',
+            has_headers: true,
+            flexible: false,
+        }
+    }
+
+    /// Tab separated values with a header
+    pub fn tsv() -> Self {
+        Self {
+            delimiter: b'\t',
+            ..Self::csv()
+        }
+    }
+
+    fn builder(&self) -> ReaderBuilder {
+        let mut b = ReaderBuilder::new();
+        b.delimiter(self.delimiter)
+            .quote(self.quote)
+            .has_headers(self.has_headers)
+            .flexible(self.flexible);
+        b
+    }
+}
+
+/// Gets a key from a record
+pub trait RecordKey: Send + Sync + 'static {
+    type K: Send + Sync + 'static;
+
+    /// What is resolved from the header once per bucket(e.g, the index of a column)
+    type Resolved: Send + 'static;
+
+    /// Resolves the header of a bucket(None: no header); a bucket is not read on error
+    fn resolve(&self, headers: Option<&StringRecord>) -> Result<Self::Resolved, Status>;
+
+    /// Gets a key from a record.
+    ///
+    /// ## Arguments
+    /// - resolved: The value returned by [`Self::resolve`] for the bucket
+    /// - ix: The index of the record(the header is not counted)
+    /// - rec: The record
+    fn key(
+        &self,
+        resolved: &Self::Resolved,
+        ix: u64,
+        rec: &StringRecord,
+    ) -> Result<Self::K, Status>;
+}
+
+/// Uses the index of a record as a key
+#[derive(Clone, Copy, Default)]
+pub struct RecordIndex {}
+
+impl RecordKey for RecordIndex {
+    type K = u64;
+    type Resolved = ();
+
+    fn resolve(&self, _: Option<&StringRecord>) -> Result<(), Status> {
+        Ok(())
+    }
+
+    fn key(&self, _: &(), ix: u64, _: &StringRecord) -> Result<u64, Status> {
+        Ok(ix)
+    }
+}
+
+/// Uses the value of a named column as a key
+#[derive(Clone)]
+pub struct NamedColumn {
+    pub name: String,
+}
+
+impl RecordKey for NamedColumn {
+    type K = String;
+
+    /// The index of the key column
+    type Resolved = usize;
+
+    fn resolve(&self, headers: Option<&StringRecord>) -> Result<usize, Status> {
+        let hdr: &StringRecord =
+            headers.ok_or_else(|| Status::invalid_argument("no header to find a key column"))?;
+        hdr.iter()
+            .position(|h| h == self.name)
+            .ok_or_else(|| Status::invalid_argument(format!("key column missing: {}", self.name)))
+    }
+
+    fn key(&self, pos: &usize, _ix: u64, rec: &StringRecord) -> Result<String, Status> {
+        rec.get(*pos).map(String::from).ok_or_else(|| {
+            Status::invalid_argument(format!("key column missing in a record: {}", self.name))
+        })
+    }
+}
+
+fn status(e: csv::Error) -> Status {
+    match e.position() {
+        Some(pos) => Status::invalid_argument(format!(
+            "invalid record at line {}(byte offset {}): {e}",
+            pos.line(),
+            pos.byte()
+        )),
+        None => Status::invalid_argument(format!("invalid record: {e}")),
+    }
+}
+
+pub struct DelimitedSrc<R, K, T> {
+    rsrc: R,
+    cfg: DelimitedConfig,
+    key: Arc<K>,
+    row: PhantomData<fn() -> T>,
+}
+
+#[tonic::async_trait]
+impl<R, K, T> BucketSource for DelimitedSrc<R, K, T>
+where
+    R: ReadSource,
+    K: RecordKey,
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    type Bucket = R::Bucket;
+    type K = K::K;
+    type V = T;
+    type All = ReceiverStream<Result<(Self::K, Self::V), Status>>;
+
+    async fn get_all_by_bucket(&self, b: Self::Bucket) -> Result<Self::All, Status> {
+        let r: R::R = self.rsrc.get_src_read_by_bucket(b).await?;
+        let bridge = SyncIoBridge::new(r);
+        let cfg: DelimitedConfig = self.cfg;
+        let key: Arc<K> = self.key.clone();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        tokio::task::spawn_blocking(move || {
+            let mut rdr = cfg.builder().from_reader(bridge);
+            let headers: Option<StringRecord> = match cfg.has_headers {
+                false => None,
+                true => match rdr.headers() {
+                    Ok(h) => Some(h.clone()),
+                    Err(e) => {
+                        let _ = tx.blocking_send(Err(status(e)));
+                        return;
+                    }
+                },
+            };
+            let hdr: Option<&StringRecord> = headers.as_ref();
+            let resolved: K::Resolved = match key.resolve(hdr) {
+                Ok(r) => r,
+                Err(e) => {
+                    let _ = tx.blocking_send(Err(e));
+                    return;
+                }
+            };
+            for (ix, rslt) in rdr.records().enumerate() {
+                let item = rslt.map_err(status).and_then(|rec| {
+                    let k: K::K = key.key(&resolved, ix as u64, &rec)?;
+                    let v: T = rec.deserialize(hdr).map_err(status)?;
+                    Ok((k, v))
+                });
+                if tx.blocking_send(item).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+}
+
+/// Creates a [`BucketSource`] which deserializes delimited records from a [`ReadSource`].
+///
+/// ## Arguments
+/// - rsrc: A [`ReadSource`] which has delimited text(e.g, a file)
+/// - cfg: The dialect(e.g, [`DelimitedConfig::tsv`])
+/// - key: A [`RecordKey`] to get a key(e.g, [`NamedColumn`], [`RecordIndex`])
+pub fn delimited_src_new<R, K, T>(
+    rsrc: R,
+    cfg: DelimitedConfig,
+    key: K,
+) -> impl BucketSource<Bucket = R::Bucket, K = K::K, V = T>
+where
+    R: ReadSource,
+    K: RecordKey,
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    DelimitedSrc {
+        rsrc,
+        cfg,
+        key: Arc::new(key),
+        row: PhantomData,
+    }
+}
+
+#[cfg(test)]
+mod test_delimited {
+    mod delimited_src_new {
+        use futures::StreamExt;
+
+        use tonic::Status;
+
+        use crate::input::delimited::{
+            delimited_src_new, DelimitedConfig, NamedColumn, RecordIndex,
+        };
+        use crate::input::source::BucketSource;
+        use crate::testing::mem_new;
+
+        type Row = (String, u32, String);
+
+        #[tokio::test]
+        async fn named_column() {
+            let src = delimited_src_new::<_, _, Row>(
+                mem_new(),
+                DelimitedConfig::csv(),
+                NamedColumn { name: "id".into() },
+            );
+            let text = "id,age,note\nk1,42,\"a, b\"\nk2,7,\"multi\nline\"\n";
+            let all = src.get_all_by_bucket(text).await.unwrap();
+            let got: Vec<(String, Row)> = all.map(|r| r.unwrap()).collect().await;
+            assert_eq!(got.len(), 2);
+            assert_eq!(got[0].0, "k1");
+            assert_eq!(got[0].1, ("k1".into(), 42, "a, b".into()));
+            assert_eq!(got[1].1 .2, "multi\nline");
+        }
+
+        #[tokio::test]
+        async fn missing_column() {
+            let src = delimited_src_new::<_, _, Row>(
+                mem_new(),
+                DelimitedConfig::csv(),
+                NamedColumn { name: "key".into() },
+            );
+            let all = src.get_all_by_bucket("id,age,note\nk1,1,x\nk2,2,y\n").await;
+            let got: Vec<Result<(String, Row), Status>> = all.unwrap().collect().await;
+            assert_eq!(got.len(), 1);
+            assert!(got[0].as_ref().unwrap_err().message().contains("key"));
+
+            let cfg = DelimitedConfig {
+                flexible: true,
+                ..DelimitedConfig::csv()
+            };
+            let src = delimited_src_new::<_, _, Row>(
+                mem_new(),
+                cfg,
+                NamedColumn {
+                    name: "note".into(),
+                },
+            );
+            let all = src.get_all_by_bucket("id,age,note\nk1,1\nk2,2,y\n").await;
+            let got: Vec<Result<(String, Row), Status>> = all.unwrap().collect().await;
+            assert_eq!(got.len(), 2);
+            assert!(got[0].is_err());
+            assert_eq!(got[1].as_ref().unwrap().0, "y");
+        }
+
+        #[tokio::test]
+        async fn record_index() {
+            let cfg = DelimitedConfig {
+                has_headers: false,
+                ..DelimitedConfig::tsv()
+            };
+            let src = delimited_src_new::<_, _, Row>(mem_new(), cfg, RecordIndex {});
+            let all = src
+                .get_all_by_bucket("k1\t1\tx\nk2\tNaN\ty\n")
+                .await
+                .unwrap();
+            let got: Vec<Result<(u64, Row), Status>> = all.collect().await;
+            assert_eq!(got[0].as_ref().unwrap().0, 0);
+            let e: &Status = got[1].as_ref().unwrap_err();
+            assert!(e.message().contains("line 2"));
+        }
+    }
+}
@@ -2,3 +2,9 @@ pub mod sync;
 
 #[cfg(feature = "async_tokio")]
 pub mod async_tokio;
+
+#[cfg(feature = "async_tokio")]
+pub mod ndjson;
+
+#[cfg(feature = "async_tokio")]
+pub mod array;
@@ -0,0 +1,243 @@
+//! A [`BucketSource`] which streams elements of a top-level JSON array
+
+use core::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use tokio_stream::wrappers::ReceiverStream;
+
+use tonic::Status;
+
+use crate::input::conv::lines::ReadSource;
+use crate::input::source::BucketSource;
+
+/// Splits a top-level JSON array into raw elements without parsing them
+#[derive(Default)]
+struct Splitter {
+    /// '[' found
+    started: bool,
+
+    /// ']' found
+    done: bool,
+
+    /// Nesting level in an element
+    depth: u64,
+
+    in_str: bool,
+    escaped: bool,
+
+    /// ',' found(an empty element before ']' is a trailing comma)
+    comma_found: bool,
+
+    elem: Vec<u8>,
+}
+
+impl Splitter {
+    fn take(&mut self) -> Result<Vec<u8>, &'static str> {
+        let elem: Vec<u8> = core::mem::take(&mut self.elem);
+        match elem.trim_ascii().is_empty() {
+            true => Err("empty element"),
+            false => Ok(elem),
+        }
+    }
+
+    /// Pushes a byte and returns a raw element if completed
+    fn push(&mut self, b: u8) -> Result<Option<Vec<u8>>, &'static str> {
+        if self.in_str {
+            self.elem.push(b);
+            match (self.escaped, b) {
+                (true, _) => self.escaped = false,
+                (false, b'\\') => self.escaped = true,
+                (false, b'"') => self.in_str = false,
+                _ => {}
+            }
+            return Ok(None);
+        }
+        if !self.started || self.done {
+            return match (b.is_ascii_whitespace(), self.started, b) {
+                (true, _, _) => Ok(None),
+                (false, false, b'[') => {
+                    self.started = true;
+                    Ok(None)
+                }
+                (false, false, _) => Err("not an array"),
+                (false, true, _) => Err("data after the array"),
+            };
+        }
+        match (self.depth, b) {
+            (0, b',') => {
+                self.comma_found = true;
+                self.take().map(Some)
+            }
+            (0, b']') => {
+                self.done = true;
+                let empty: bool = self.elem.trim_ascii().is_empty();
+                match (empty, self.comma_found) {
+                    (true, false) => Ok(None),
+                    _ => self.take().map(Some),
+                }
+            }
+            (_, b'"') => {
+                self.in_str = true;
+                self.elem.push(b);
+                Ok(None)
+            }
+            (_, b'{') | (_, b'[') => {
+                self.depth += 1;
+                self.elem.push(b);
+                Ok(None)
+            }
+            (0, b'}') => Err("unbalanced brackets"),
+            (_, b'}') | (_, b']') => {
+                self.depth -= 1;
+                self.elem.push(b);
+                Ok(None)
+            }
+            _ => {
+                self.elem.push(b);
+                Ok(None)
+            }
+        }
+    }
+}
+
+fn invalid(msg: &str, offset: u64) -> Status {
+    Status::invalid_argument(format!("{msg} at byte offset {offset}"))
+}
+
+pub struct JsonArraySrc<R, T> {
+    rsrc: R,
+    row: PhantomData<fn() -> T>,
+}
+
+#[tonic::async_trait]
+impl<R, T> BucketSource for JsonArraySrc<R, T>
+where
+    R: ReadSource,
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    type Bucket = R::Bucket;
+    type K = usize;
+    type V = T;
+    type All = ReceiverStream<Result<(Self::K, Self::V), Status>>;
+
+    /// Gets all elements with their indices(stops at the first syntax error)
+    async fn get_all_by_bucket(&self, b: Self::Bucket) -> Result<Self::All, Status> {
+        let r: R::R = self.rsrc.get_src_read_by_bucket(b).await?;
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        tokio::spawn(async move {
+            let mut br = BufReader::new(r);
+            let mut sp = Splitter::default();
+            let mut offset: u64 = 0;
+            let mut ix: usize = 0;
+            loop {
+                let chunk: &[u8] = match br.fill_buf().await {
+                    Ok(c) => c,
+                    Err(e) => {
+                        let msg: String = format!("unable to read: {e}");
+                        let _ = tx.send(Err(invalid(&msg, offset))).await;
+                        return;
+                    }
+                };
+                if chunk.is_empty() {
+                    if !sp.done {
+                        let _ = tx.send(Err(invalid("unexpected end", offset))).await;
+                    }
+                    return;
+                }
+                let mut elems: Vec<Result<(usize, T), Status>> = vec![];
+                let mut failed: bool = false;
+                for b in chunk {
+                    match sp.push(*b) {
+                        Ok(None) => {}
+                        Ok(Some(raw)) => {
+                            let parsed = serde_json::from_slice(&raw).map_err(|e| {
+                                invalid(&format!("invalid element {ix}: {e}"), offset)
+                            });
+                            elems.push(parsed.map(|t: T| (ix, t)));
+                            ix += 1;
+                        }
+                        Err(msg) => {
+                            elems.push(Err(invalid(msg, offset)));
+                            failed = true;
+                            break;
+                        }
+                    }
+                    offset += 1;
+                }
+                let sz: usize = chunk.len();
+                br.consume(sz);
+                for item in elems {
+                    if tx.send(item).await.is_err() {
+                        return;
+                    }
+                }
+                if failed {
+                    return;
+                }
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+}
+
+/// Creates a [`BucketSource`] which yields elements of a JSON array from a [`ReadSource`].
+///
+/// Only one element is kept in memory at a time; keys are indices of elements.
+pub fn json_array_src_new<R, T>(rsrc: R) -> impl BucketSource<Bucket = R::Bucket, K = usize, V = T>
+where
+    R: ReadSource,
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    JsonArraySrc {
+        rsrc,
+        row: PhantomData,
+    }
+}
+
+#[cfg(test)]
+mod test_array {
+    mod json_array_src_new {
+        use futures::StreamExt;
+
+        use serde_json::Value;
+
+        use tonic::Status;
+
+        use crate::input::lines::json::array::json_array_src_new;
+        use crate::input::source::BucketSource;
+        use crate::testing::mem_new;
+
+        async fn all(text: &'static str) -> Vec<Result<(usize, Value), Status>> {
+            let src = json_array_src_new::<_, Value>(mem_new());
+            src.get_all_by_bucket(text).await.unwrap().collect().await
+        }
+
+        #[tokio::test]
+        async fn elements() {
+            let got = all(r#" [ {"a":"],\"}"}, [1,[2]], "x", 42 , true ] "#).await;
+            let vals: Vec<Value> = got.into_iter().map(|r| r.unwrap().1).collect();
+            assert_eq!(vals.len(), 5);
+            assert_eq!(vals[0]["a"], "],\"}");
+            assert_eq!(vals[1][1][0], 2);
+            assert_eq!(vals[3], 42);
+        }
+
+        #[tokio::test]
+        async fn empty() {
+            assert!(all("[]").await.is_empty());
+        }
+
+        #[tokio::test]
+        async fn invalid() {
+            let trailing = all("[1,]").await;
+            assert!(trailing[1].is_err());
+            let truncated = all("[1, 2").await;
+            assert_eq!(truncated.len(), 2);
+            assert!(truncated[1].as_ref().unwrap_err().message().contains("end"));
+            assert!(all("{}").await[0].is_err());
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! A [`BucketSource`] which deserializes newline delimited JSON
+
+use core::marker::PhantomData;
+
+use std::sync::Arc;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use tokio_stream::wrappers::ReceiverStream;
+
+use tonic::Status;
+
+use crate::input::conv::lines::ReadSource;
+use crate::input::source::BucketSource;
+
+/// Selects a key from a JSON value
+pub trait KeySelector: Send + Sync + 'static {
+    type K: Send + Sync + 'static;
+
+    fn select(&self, v: &Value) -> Result<Self::K, Status>;
+}
+
+/// Selects a key using a JSON pointer(e.g, "/id", "/user/name").
+///
+/// A string value is used as is; other values are serialized(e.g, 42 -> "42").
+#[derive(Clone)]
+pub struct JsonPointer {
+    pub pointer: String,
+}
+
+impl KeySelector for JsonPointer {
+    type K = String;
+
+    fn select(&self, v: &Value) -> Result<Self::K, Status> {
+        match v.pointer(&self.pointer) {
+            None => Err(Status::invalid_argument(format!(
+                "key missing: {}",
+                self.pointer
+            ))),
+            Some(Value::String(s)) => Ok(s.clone()),
+            Some(k) => Ok(k.to_string()),
+        }
+    }
+}
+
+impl<F, K> KeySelector for F
+where
+    F: Fn(&Value) -> Result<K, Status> + Send + Sync + 'static,
+    K: Send + Sync + 'static,
+{
+    type K = K;
+
+    fn select(&self, v: &Value) -> Result<Self::K, Status> {
+        self(v)
+    }
+}
+
+/// Location of a line
+#[derive(Debug, Clone, Copy)]
+struct Loc {
+    /// Line number(starts with 1)
+    line: u64,
+
+    /// Byte offset of the line
+    offset: u64,
+}
+
+impl Loc {
+    fn status(&self, msg: &str) -> Status {
+        Status::invalid_argument(format!(
+            "{msg} at line {}(byte offset {})",
+            self.line, self.offset
+        ))
+    }
+}
+
+fn parse<K, T>(ks: &K, line: &[u8], loc: Loc) -> Result<(K::K, T), Status>
+where
+    K: KeySelector,
+    T: DeserializeOwned,
+{
+    let v: Value =
+        serde_json::from_slice(line).map_err(|e| loc.status(&format!("invalid json: {e}")))?;
+    let key: K::K = ks
+        .select(&v)
+        .map_err(|e| loc.status(&format!("no key: {}", e.message())))?;
+    let t: T =
+        serde_json::from_value(v).map_err(|e| loc.status(&format!("unexpected json: {e}")))?;
+    Ok((key, t))
+}
+
+pub struct NdJsonSrc<R, K, T> {
+    rsrc: R,
+    ks: Arc<K>,
+    row: PhantomData<fn() -> T>,
+}
+
+#[tonic::async_trait]
+impl<R, K, T> BucketSource for NdJsonSrc<R, K, T>
+where
+    R: ReadSource,
+    K: KeySelector,
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    type Bucket = R::Bucket;
+    type K = K::K;
+    type V = T;
+    type All = ReceiverStream<Result<(Self::K, Self::V), Status>>;
+
+    /// Gets all deserialized values(blank lines are skipped)
+    async fn get_all_by_bucket(&self, b: Self::Bucket) -> Result<Self::All, Status> {
+        let r: R::R = self.rsrc.get_src_read_by_bucket(b).await?;
+        let ks: Arc<K> = self.ks.clone();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        tokio::spawn(async move {
+            let mut br = BufReader::new(r);
+            let mut buf: Vec<u8> = vec![];
+            let mut loc = Loc { line: 0, offset: 0 };
+            loop {
+                buf.clear();
+                loc.line += 1;
+                let sz: usize = match br.read_until(b'\n', &mut buf).await {
+                    Ok(0) => return,
+                    Ok(sz) => sz,
+                    Err(e) => {
+                        let _ = tx
+                            .send(Err(loc.status(&format!("unable to read: {e}"))))
+                            .await;
+                        return;
+                    }
+                };
+                let line: &[u8] = buf.trim_ascii();
+                if !line.is_empty() && tx.send(parse(ks.as_ref(), line, loc)).await.is_err() {
+                    return;
+                }
+                loc.offset += sz as u64;
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+}
+
+/// Creates a [`BucketSource`] which deserializes JSON lines from a [`ReadSource`].
+///
+/// ## Arguments
+/// - rsrc: A [`ReadSource`] which has JSON lines
+/// - ks: A [`KeySelector`](e.g, [`JsonPointer`], a closure) to get a key from a JSON value
+pub fn ndjson_src_new<R, K, T>(
+    rsrc: R,
+    ks: K,
+) -> impl BucketSource<Bucket = R::Bucket, K = K::K, V = T>
+where
+    R: ReadSource,
+    K: KeySelector,
+    T: DeserializeOwned + Send + Sync + 'static,
+{
+    NdJsonSrc {
+        rsrc,
+        ks: Arc::new(ks),
+        row: PhantomData,
+    }
+}
+
+#[cfg(test)]
+mod test_ndjson {
+    mod ndjson_src_new {
+        use futures::StreamExt;
+        use std::collections::BTreeMap;
+
+        use serde_json::Value;
+
+        use tonic::Status;
+
+        use crate::input::lines::json::ndjson::{ndjson_src_new, JsonPointer};
+        use crate::input::source::BucketSource;
+        use crate::testing::mem_new;
+
+        type Row = BTreeMap<String, Value>;
+
+        #[tokio::test]
+        async fn json_pointer() {
+            let src = ndjson_src_new::<_, _, Row>(
+                mem_new(),
+                JsonPointer {
+                    pointer: "/id".into(),
+                },
+            );
+            let text = "{\"id\":\"k1\",\"v\":1}\n\n{\"id\":2,\"v\":2}\n{\"id\":\nbroken\n";
+            let all = src.get_all_by_bucket(text).await.unwrap();
+            let got: Vec<Result<(String, Row), Status>> = all.collect().await;
+            assert_eq!(got.len(), 4);
+            assert_eq!(got[0].as_ref().unwrap().0, "k1");
+            assert_eq!(got[1].as_ref().unwrap().0, "2");
+            let e: &Status = got[2].as_ref().unwrap_err();
+            assert!(e.message().contains("line 4(byte offset 34)"));
+        }
+
+        #[tokio::test]
+        async fn closure() {
+            let ks = |v: &Value| {
+                v["n"]
+                    .as_u64()
+                    .ok_or_else(|| Status::invalid_argument("n missing"))
+            };
+            type Pair = (u64, (u64, String));
+            let src = ndjson_src_new::<_, _, (u64, String)>(mem_new(), ks);
+            let got: Vec<Result<Pair, Status>> = src
+                .get_all_by_bucket("{\"n\":7}\n")
+                .await
+                .unwrap()
+                .collect()
+                .await;
+            let e: &Status = got[0].as_ref().unwrap_err();
+            assert!(e.message().contains("unexpected json"));
+
+            let nested = |v: &Value| {
+                v["user"]["id"]
+                    .as_u64()
+                    .ok_or_else(|| Status::invalid_argument("user id missing"))
+            };
+            let src = ndjson_src_new::<_, _, Row>(mem_new(), nested);
+            let text = "{\"user\":{\"id\":3},\"v\":1}\n{\"user\":{},\"v\":2}\n";
+            let got: Vec<Result<(u64, Row), Status>> =
+                src.get_all_by_bucket(text).await.unwrap().collect().await;
+            assert_eq!(got.len(), 2);
+            let (key, row) = got[0].as_ref().unwrap();
+            assert_eq!(*key, 3);
+            assert_eq!(row["v"], Value::from(1));
+            let e: &Status = got[1].as_ref().unwrap_err();
+            assert!(e.message().contains("no key: user id missing"));
+        }
+    }
+}
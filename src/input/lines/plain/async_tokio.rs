@@ -6,6 +6,10 @@ use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio_stream::wrappers::{LinesStream, SplitStream};
 use tokio_stream::Stream;
 
+use tonic::Status;
+
+use crate::input::conv::lines::delim::{Delimiter, MaxLen, Next, Splitter};
+
 async fn path2file<P>(p: P) -> Result<File, io::Error>
 where
     P: AsRef<Path>,
@@ -37,3 +41,86 @@ where
     let splited = br.split(b'\n');
     Ok(SplitStream::new(splited))
 }
+
+/// Splits lines like [`path2slices`] but limits the length of a line(without the newline).
+///
+/// Bytes of a long line are discarded while reading(never buffered).
+/// A long line is handled by [`MaxLen::too_long`](see [`Splitter::next`]):
+/// a rejected line is sent as an item error keyed by its index(big endian);
+/// a failure is sent as [`Status::resource_exhausted`] with the byte offset and ends the stream.
+pub async fn path2slices_max<P>(
+    p: P,
+    max_len: MaxLen,
+) -> Result<impl Stream<Item = Result<Vec<u8>, Status>>, io::Error>
+where
+    P: AsRef<Path>,
+{
+    let f: File = path2file(p).await?;
+    let br = BufReader::new(f);
+    let splitter = Splitter {
+        delim: Delimiter::default(),
+        max_len: Some(max_len),
+    };
+    Ok(futures::stream::unfold(Some((br, 0, 0)), move |state| {
+        let splitter = splitter.clone();
+        async move {
+            let (mut br, offset, ix): (BufReader<File>, u64, u64) = state?;
+            let mut buf: Vec<u8> = vec![];
+            let key: [u8; 8] = ix.to_be_bytes();
+            match splitter.next(&mut br, &mut buf, offset, &key).await {
+                Next::End => None,
+                Next::Record(cnt) => Some((Ok(buf), Some((br, offset + cnt as u64, ix + 1)))),
+                Next::Skip(cnt, e) => Some((Err(e), Some((br, offset + cnt as u64, ix + 1)))),
+                Next::Stop(e) => Some((Err(e), None)),
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod test_async_tokio {
+    mod path2slices_max {
+        use std::path::PathBuf;
+
+        use futures::StreamExt;
+
+        use tonic::{Code, Status};
+
+        use crate::input::conv::lines::delim::{MaxLen, TooLong};
+        use crate::input::lines::plain::async_tokio::path2slices_max;
+        use crate::output::dead::Rejected;
+        use crate::testing::TempDir;
+
+        async fn lines(p: &PathBuf, too_long: TooLong) -> Vec<Result<Vec<u8>, Status>> {
+            let max_len = MaxLen { max: 3, too_long };
+            let s = path2slices_max(p, max_len).await.unwrap();
+            s.collect().await
+        }
+
+        #[tokio::test]
+        async fn too_long() {
+            let dir = TempDir::new("slices");
+            let p: PathBuf = dir.join("lines.txt");
+            std::fs::write(&p, b"ab\nwxyz\ncd\n").unwrap();
+
+            let got = lines(&p, TooLong::Truncate).await;
+            let got: Vec<Vec<u8>> = got.into_iter().map(|r| r.unwrap()).collect();
+            assert_eq!(got, vec![b"ab".to_vec(), b"wxy".to_vec(), b"cd".to_vec()]);
+
+            let mut got = lines(&p, TooLong::Reject).await.into_iter();
+            assert_eq!(got.next().unwrap().unwrap(), b"ab");
+            let r = Rejected::from_status(vec![], got.next().unwrap().unwrap_err());
+            assert_eq!(r.status.code(), Code::ResourceExhausted);
+            assert_eq!(r.key, 1u64.to_be_bytes());
+            assert_eq!(r.raw, b"wxy");
+            assert_eq!(got.next().unwrap().unwrap(), b"cd");
+            assert!(got.next().is_none());
+
+            let got = lines(&p, TooLong::Fail).await;
+            assert_eq!(got.len(), 2);
+            let e: &Status = got[1].as_ref().unwrap_err();
+            assert_eq!(e.code(), Code::ResourceExhausted);
+            assert!(e.message().contains(" 3 "));
+        }
+    }
+}
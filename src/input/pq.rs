@@ -0,0 +1,223 @@
+//! A [`BucketSource`] which reads rows of a Parquet file
+//!
+//! Row groups are read one by one on a blocking thread using the record API.
+
+use std::fs::File;
+use std::sync::Arc;
+
+use tokio_stream::wrappers::ReceiverStream;
+
+use tonic::Status;
+
+use parquet::errors::ParquetError;
+use parquet::file::reader::{FileReader, RowGroupReader, SerializedFileReader};
+use parquet::record::{Field, Row};
+use parquet::schema::types::{Type, TypePtr};
+
+use crate::input::conv::lines::FsSource;
+use crate::input::source::BucketSource;
+
+/// Columns to read
+#[derive(Debug, Clone)]
+pub struct ParquetConfig {
+    /// The name of the key column
+    pub key: String,
+
+    /// Names of value columns(None: all columns including the key column)
+    pub projection: Option<Vec<String>>,
+}
+
+fn status(e: ParquetError) -> Status {
+    Status::internal(format!("unable to read a parquet file: {e}"))
+}
+
+impl ParquetConfig {
+    fn is_value(&self, name: &str) -> bool {
+        match &self.projection {
+            None => true,
+            Some(names) => names.iter().any(|n| n == name),
+        }
+    }
+
+    /// Creates a schema to read the key column and value columns
+    fn schema(&self, root: &Type) -> Result<Type, Status> {
+        let fields: Vec<TypePtr> = root
+            .get_fields()
+            .iter()
+            .filter(|f| f.name() == self.key || self.is_value(f.name()))
+            .cloned()
+            .collect();
+        let found = |name: &str| fields.iter().any(|f| f.name() == name);
+        let missing: Option<&String> = core::iter::once(&self.key)
+            .chain(self.projection.iter().flatten())
+            .find(|name| !found(name));
+        if let Some(name) = missing {
+            return Err(Status::invalid_argument(format!("no such column: {name}")));
+        }
+        Type::group_type_builder(root.name())
+            .with_fields(fields)
+            .build()
+            .map_err(status)
+    }
+
+    /// Splits a row into the key and the projected row
+    fn split(&self, row: Row) -> Result<(Field, Row), Status> {
+        let cols: Vec<(String, Field)> = row.into_columns();
+        let key: Field = cols
+            .iter()
+            .find(|pair| pair.0 == self.key)
+            .map(|pair| pair.1.clone())
+            .ok_or_else(|| Status::invalid_argument(format!("key missing: {}", self.key)))?;
+        let vals: Vec<(String, Field)> = cols
+            .into_iter()
+            .filter(|pair| self.is_value(&pair.0))
+            .collect();
+        Ok((key, Row::new(vals)))
+    }
+}
+
+fn read_all(
+    f: File,
+    cfg: &ParquetConfig,
+    tx: &tokio::sync::mpsc::Sender<Result<(Field, Row), Status>>,
+) -> Result<(), Status> {
+    let rdr = SerializedFileReader::new(f).map_err(status)?;
+    let root: &Type = rdr.metadata().file_metadata().schema();
+    let schema: Type = cfg.schema(root)?;
+    for ix in 0..rdr.num_row_groups() {
+        let rg: Box<dyn RowGroupReader + '_> = rdr.get_row_group(ix).map_err(status)?;
+        let rows = rg.get_row_iter(Some(schema.clone())).map_err(status)?;
+        for rslt in rows {
+            let item = rslt.map_err(status).and_then(|row| cfg.split(row));
+            if tx.blocking_send(item).is_err() {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+pub struct ParquetSrc<F> {
+    fsrc: F,
+    cfg: Arc<ParquetConfig>,
+}
+
+#[tonic::async_trait]
+impl<F> BucketSource for ParquetSrc<F>
+where
+    F: FsSource,
+{
+    type Bucket = F::Bucket;
+    type K = Field;
+    type V = Row;
+    type All = ReceiverStream<Result<(Self::K, Self::V), Status>>;
+
+    async fn get_all_by_bucket(&self, b: Self::Bucket) -> Result<Self::All, Status> {
+        let f: File = self.fsrc.get_file_by_bucket(b).await?.into_std().await;
+        let cfg: Arc<ParquetConfig> = self.cfg.clone();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = read_all(f, &cfg, &tx) {
+                let _ = tx.blocking_send(Err(e));
+            }
+        });
+        Ok(ReceiverStream::new(rx))
+    }
+}
+
+/// Creates a [`BucketSource`] which reads a Parquet file per bucket.
+///
+/// ## Arguments
+/// - fsrc: An [`FsSource`] which resolves a bucket to a Parquet file
+/// - cfg: The key column and value columns
+pub fn parquet_src_new<F>(
+    fsrc: F,
+    cfg: ParquetConfig,
+) -> impl BucketSource<Bucket = F::Bucket, K = Field, V = Row>
+where
+    F: FsSource,
+{
+    ParquetSrc {
+        fsrc,
+        cfg: Arc::new(cfg),
+    }
+}
+
+#[cfg(test)]
+mod test_pq {
+    mod parquet_src_new {
+        use std::path::PathBuf;
+        use std::sync::Arc;
+
+        use futures::StreamExt;
+
+        use tonic::Status;
+
+        use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+        use parquet::file::writer::SerializedFileWriter;
+        use parquet::record::{Field, Row};
+        use parquet::schema::parser::parse_message_type;
+
+        use crate::input::pq::{parquet_src_new, ParquetConfig};
+        use crate::input::source::BucketSource;
+        use crate::testing::TempDir;
+
+        fn write(p: &PathBuf) {
+            let schema = parse_message_type(
+                "message m { required int64 id; required binary name (UTF8); required int64 age; }",
+            )
+            .unwrap();
+            let f = std::fs::File::create(p).unwrap();
+            let mut w = SerializedFileWriter::new(f, Arc::new(schema), Default::default()).unwrap();
+            for (ids, names) in [(vec![1, 2], vec!["a", "b"]), (vec![3], vec!["c"])] {
+                let mut rg = w.next_row_group().unwrap();
+                let mut c = rg.next_column().unwrap().unwrap();
+                c.typed::<Int64Type>()
+                    .write_batch(&ids, None, None)
+                    .unwrap();
+                c.close().unwrap();
+                let names: Vec<ByteArray> = names.into_iter().map(ByteArray::from).collect();
+                let mut c = rg.next_column().unwrap().unwrap();
+                c.typed::<ByteArrayType>()
+                    .write_batch(&names, None, None)
+                    .unwrap();
+                c.close().unwrap();
+                let ages: Vec<i64> = ids.iter().map(|i| i * 10).collect();
+                let mut c = rg.next_column().unwrap().unwrap();
+                c.typed::<Int64Type>()
+                    .write_batch(&ages, None, None)
+                    .unwrap();
+                c.close().unwrap();
+                rg.close().unwrap();
+            }
+            w.close().unwrap();
+        }
+
+        #[tokio::test]
+        async fn projected() {
+            let dir = TempDir::new("parquet");
+            write(&dir.join("m.parquet"));
+
+            let cfg = ParquetConfig {
+                key: "id".into(),
+                projection: Some(vec!["name".into()]),
+            };
+            let src = parquet_src_new(dir.dir(), cfg);
+            let all = src.get_all_by_bucket("m.parquet").await.unwrap();
+            let got: Vec<(Field, Row)> = all.map(|r| r.unwrap()).collect().await;
+            assert_eq!(got.len(), 3);
+            assert_eq!(got[2].0, Field::Long(3));
+            let cols: Vec<(String, Field)> = got[2].1.clone().into_columns();
+            assert_eq!(cols, vec![("name".into(), Field::Str("c".into()))]);
+
+            let missing = ParquetConfig {
+                key: "nope".into(),
+                projection: None,
+            };
+            let src = parquet_src_new(dir.dir(), missing);
+            let all = src.get_all_by_bucket("m.parquet").await.unwrap();
+            let got: Vec<Result<(Field, Row), Status>> = all.collect().await;
+            assert!(got[0].is_err());
+        }
+    }
+}
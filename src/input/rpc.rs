@@ -3,6 +3,8 @@ use tonic::{transport::Channel, Request, Response, Status};
 
 use crate::input::select::Select;
 
+pub mod server;
+
 use crate::rpc::fs2db::proto::source;
 use source::v1::select_service_client::SelectServiceClient;
 use source::v1::{AllRequest, AllResponse, InputBucket};
@@ -20,6 +22,7 @@ impl Select for SelectClient {
     async fn all(&self, bucket: Vec<u8>) -> Result<Self::Rows, Status> {
         let req = AllRequest {
             bkt: Some(InputBucket { bucket }),
+            start_after: vec![],
         };
         let res: Response<_> = self.cli.clone().all(Request::new(req)).await?;
         let s: Streaming<_> = res.into_inner();
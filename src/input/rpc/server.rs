@@ -0,0 +1,271 @@
+//! Serves a [`BucketSource`] as a source select service
+
+use std::sync::Arc;
+
+use futures::StreamExt;
+
+use tokio_stream::wrappers::ReceiverStream;
+
+use tonic::{Request, Response, Status};
+
+use crate::codec::{Decoder, Encoder};
+use crate::input::source::BucketSource;
+use crate::item::item_key;
+
+use crate::rpc::fs2db::proto::source;
+use source::v1::select_service_server::SelectService;
+use source::v1::{AllRequest, AllResponse, InputBucket, Rejection};
+
+/// Creates a response which rejects a row(the key and the raw bytes are taken from the status)
+fn rejection(s: Status) -> AllResponse {
+    AllResponse {
+        key: item_key(&s),
+        val: s.details().to_vec(),
+        rejected: Some(Rejection {
+            code: s.code().into(),
+            message: s.message().into(),
+        }),
+    }
+}
+
+pub struct BucketSvc<B, D, KE, VE> {
+    source: B,
+    bdec: D,
+    kenc: Arc<KE>,
+    venc: Arc<VE>,
+}
+
+#[tonic::async_trait]
+impl<B, D, KE, VE> SelectService for BucketSvc<B, D, KE, VE>
+where
+    B: BucketSource,
+    D: Decoder<T = B::Bucket>,
+    KE: Encoder<T = B::K>,
+    VE: Encoder<T = B::V>,
+{
+    type AllStream = ReceiverStream<Result<AllResponse, Status>>;
+
+    /// Gets all encoded key/val pairs from a decoded bucket.
+    ///
+    /// Row failures(errors of the source stream and encode errors) are sent in-band as rejected
+    /// pairs(see [`item_status`](crate::item::item_status)) so that the rest of the bucket is
+    /// still sent; an error of the RPC(e.g, an invalid bucket) is fatal.
+    ///
+    /// Pairs whose encoded key is less than or equal to start_after are skipped.
+    /// The skipped pairs are still read and encoded(the source does not know encoded keys);
+    /// resuming a large bucket near its end costs as much as reading the whole bucket.
+    /// Use a source which takes the position in the bucket to resume cheaply.
+    ///
+    /// Stops reading the bucket when the client goes away.
+    async fn all(&self, req: Request<AllRequest>) -> Result<Response<Self::AllStream>, Status> {
+        let ar: AllRequest = req.into_inner();
+        let bkt: InputBucket = ar
+            .bkt
+            .ok_or_else(|| Status::invalid_argument("input bucket missing"))?;
+        let decoded: B::Bucket = self.bdec.decode(bkt.bucket)?;
+        let all: B::All = self.source.get_all_by_bucket(decoded).await?;
+        let start_after: Vec<u8> = ar.start_after;
+        let kenc: Arc<KE> = self.kenc.clone();
+        let venc: Arc<VE> = self.venc.clone();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        tokio::spawn(async move {
+            let encoded = all.map(|rslt| {
+                rslt.and_then(|pair| {
+                    let (k, v) = pair;
+                    let key: Vec<u8> = kenc.encode(k)?;
+                    let val: Vec<u8> = venc.encode(v)?;
+                    Ok(AllResponse {
+                        key,
+                        val,
+                        rejected: None,
+                    })
+                })
+                .unwrap_or_else(rejection)
+            });
+            let rs: &[u8] = &start_after;
+            let resumed = encoded
+                .filter(|a| {
+                    let unknown: bool = a.rejected.is_some() && a.key.is_empty();
+                    let skip: bool = !rs.is_empty() && a.key.as_slice() <= rs && !unknown;
+                    futures::future::ready(!skip)
+                })
+                .map(Ok);
+            let mut pinned = Box::pin(resumed);
+            while let Some(item) = pinned.next().await {
+                if tx.send(item).await.is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+/// Creates a source select service from a [`BucketSource`] and codecs.
+///
+/// ## Arguments
+/// - source: A [`BucketSource`] which must return sorted keys(in encoded bytes) to resume
+/// - bdec: A [`Decoder`] which decodes a bucket received over gRPC
+/// - kenc: An [`Encoder`] which encodes keys
+/// - venc: An [`Encoder`] which encodes values
+pub fn bucket_svc_new<B, D, KE, VE>(source: B, bdec: D, kenc: KE, venc: VE) -> impl SelectService
+where
+    B: BucketSource,
+    D: Decoder<T = B::Bucket>,
+    KE: Encoder<T = B::K>,
+    VE: Encoder<T = B::V>,
+{
+    BucketSvc {
+        source,
+        bdec,
+        kenc: Arc::new(kenc),
+        venc: Arc::new(venc),
+    }
+}
+
+#[cfg(test)]
+mod test_server {
+    mod bucket_svc_new {
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        use futures::StreamExt;
+
+        use tokio_stream::wrappers::ReceiverStream;
+
+        use tonic::{Code, Request, Status};
+
+        use crate::codec::{BigEndian, Utf8};
+        use crate::input::rpc::server::bucket_svc_new;
+        use crate::input::source::BucketSource;
+        use crate::item::item_status;
+
+        use crate::rpc::fs2db::proto::source;
+        use source::v1::select_service_server::SelectService;
+        use source::v1::{AllRequest, AllResponse, InputBucket};
+
+        struct Letters {}
+
+        #[tonic::async_trait]
+        impl BucketSource for Letters {
+            type Bucket = String;
+            type K = u32;
+            type V = String;
+            type All = ReceiverStream<Result<(Self::K, Self::V), Status>>;
+
+            async fn get_all_by_bucket(&self, b: Self::Bucket) -> Result<Self::All, Status> {
+                let (tx, rx) = tokio::sync::mpsc::channel(4);
+                for (ix, c) in b.chars().enumerate() {
+                    let key: [u8; 4] = (ix as u32).to_be_bytes();
+                    let item = match c {
+                        '!' => Err(item_status(
+                            Code::InvalidArgument,
+                            "bad letter".into(),
+                            &key,
+                            vec![b'!'],
+                        )),
+                        _ => Ok((ix as u32, c.into())),
+                    };
+                    tx.send(item).await.unwrap();
+                }
+                Ok(ReceiverStream::new(rx))
+            }
+        }
+
+        /// Sends pairs until the receiver goes away
+        struct Endless {
+            stopped: Arc<tokio::sync::Notify>,
+        }
+
+        #[tonic::async_trait]
+        impl BucketSource for Endless {
+            type Bucket = String;
+            type K = u32;
+            type V = String;
+            type All = ReceiverStream<Result<(Self::K, Self::V), Status>>;
+
+            async fn get_all_by_bucket(&self, _: Self::Bucket) -> Result<Self::All, Status> {
+                let stopped = self.stopped.clone();
+                let (tx, rx) = tokio::sync::mpsc::channel(1);
+                tokio::spawn(async move {
+                    for ix in 0u32.. {
+                        if tx.send(Ok((ix, "x".into()))).await.is_err() {
+                            break;
+                        }
+                    }
+                    stopped.notify_one();
+                });
+                Ok(ReceiverStream::new(rx))
+            }
+        }
+
+        async fn all_in(bucket: &[u8], start_after: Vec<u8>) -> Vec<AllResponse> {
+            let svc = bucket_svc_new(Letters {}, Utf8 {}, BigEndian::<u32>::default(), Utf8 {});
+            let req = AllRequest {
+                bkt: Some(InputBucket {
+                    bucket: bucket.to_vec(),
+                }),
+                start_after,
+            };
+            let res = svc.all(Request::new(req)).await.unwrap().into_inner();
+            res.map(|r| r.unwrap()).collect().await
+        }
+
+        async fn all(start_after: Vec<u8>) -> Vec<AllResponse> {
+            all_in(b"abc", start_after).await
+        }
+
+        #[tokio::test]
+        async fn encoded() {
+            let got: Vec<AllResponse> = all(vec![]).await;
+            assert_eq!(got.len(), 3);
+            assert_eq!(got[0].key, vec![0, 0, 0, 0]);
+            assert_eq!(got[2].key, vec![0, 0, 0, 2]);
+            assert_eq!(got[2].val, b"c".to_vec());
+        }
+
+        #[tokio::test]
+        async fn resumed() {
+            let got: Vec<AllResponse> = all(vec![0, 0, 0, 1]).await;
+            assert_eq!(got.len(), 1);
+            assert_eq!(got[0].val, b"c".to_vec());
+        }
+
+        #[tokio::test]
+        async fn rejected() {
+            let got: Vec<AllResponse> = all_in(b"a!c", vec![]).await;
+            assert_eq!(got.len(), 3);
+            assert!(got[0].rejected.is_none());
+            assert!(got[2].rejected.is_none());
+            assert_eq!(got[2].val, b"c".to_vec());
+
+            let r = got[1].rejected.as_ref().unwrap();
+            assert_eq!(Code::from(r.code), Code::InvalidArgument);
+            assert_eq!(got[1].key, vec![0, 0, 0, 1]);
+            assert_eq!(got[1].val, b"!".to_vec());
+
+            let got: Vec<AllResponse> = all_in(b"a!c", vec![0, 0, 0, 1]).await;
+            assert_eq!(got.len(), 1);
+            assert_eq!(got[0].val, b"c".to_vec());
+        }
+
+        #[tokio::test]
+        async fn disconnected() {
+            let stopped = Arc::new(tokio::sync::Notify::new());
+            let src = Endless {
+                stopped: stopped.clone(),
+            };
+            let svc = bucket_svc_new(src, Utf8 {}, BigEndian::<u32>::default(), Utf8 {});
+            let req = AllRequest {
+                bkt: Some(InputBucket { bucket: vec![] }),
+                start_after: vec![],
+            };
+            let res = svc.all(Request::new(req)).await.unwrap().into_inner();
+            let got: Vec<_> = res.take(2).collect().await;
+            assert_eq!(got.len(), 2);
+
+            let waited = tokio::time::timeout(Duration::from_secs(5), stopped.notified()).await;
+            assert!(waited.is_ok(), "the bucket is still read");
+        }
+    }
+}
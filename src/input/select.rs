@@ -10,3 +10,100 @@ pub trait Select {
 
     async fn all(&self, b: Self::Bucket) -> Result<Self::Rows, Status>;
 }
+
+/// A [`Select`] which retries failed calls of [`Select::all`].
+///
+/// Only opening the rows is retried; errors in the rows are returned as is.
+#[cfg(feature = "async_tokio")]
+pub struct Retried<S> {
+    pub inner: S,
+    pub policy: crate::retry::RetryPolicy,
+}
+
+#[cfg(feature = "async_tokio")]
+#[tonic::async_trait]
+impl<S> Select for Retried<S>
+where
+    S: Select + Sync,
+    S::Bucket: Clone + Send + Sync,
+    S::Rows: Send,
+{
+    type Bucket = S::Bucket;
+    type Row = S::Row;
+    type Rows = S::Rows;
+
+    async fn all(&self, b: Self::Bucket) -> Result<Self::Rows, Status> {
+        let bref: &S::Bucket = &b;
+        crate::retry::retry(&self.policy, || self.inner.all(bref.clone())).await
+    }
+}
+
+#[cfg(all(test, feature = "async_tokio"))]
+mod test_select {
+    mod retried {
+        use std::sync::atomic::{AtomicU32, Ordering};
+        use std::time::Duration;
+
+        use futures::StreamExt;
+
+        use tonic::{Code, Status};
+
+        use crate::input::select::{Retried, Select};
+        use crate::retry::RetryPolicy;
+
+        type Rows = futures::stream::Iter<std::vec::IntoIter<Result<u32, Status>>>;
+
+        /// Fails to open rows until the number of failures reached
+        struct Flaky {
+            fails: u32,
+            code: Code,
+            calls: AtomicU32,
+        }
+
+        #[tonic::async_trait]
+        impl Select for Flaky {
+            type Bucket = u32;
+            type Row = u32;
+            type Rows = Rows;
+
+            async fn all(&self, b: Self::Bucket) -> Result<Self::Rows, Status> {
+                let called: u32 = 1 + self.calls.fetch_add(1, Ordering::SeqCst);
+                match called <= self.fails {
+                    true => Err(Status::new(self.code, "failed")),
+                    false => Ok(futures::stream::iter(vec![Ok(b), Ok(b + 1)])),
+                }
+            }
+        }
+
+        fn retried(fails: u32, code: Code) -> Retried<Flaky> {
+            Retried {
+                inner: Flaky {
+                    fails,
+                    code,
+                    calls: AtomicU32::new(0),
+                },
+                policy: RetryPolicy {
+                    max_attempts: 3,
+                    initial: Duration::from_millis(1),
+                    ..Default::default()
+                },
+            }
+        }
+
+        #[tokio::test]
+        async fn recovered() {
+            let r = retried(2, Code::Unavailable);
+            let rows: Vec<u32> = r.all(7).await.unwrap().map(|r| r.unwrap()).collect().await;
+            assert_eq!(rows, vec![7, 8]);
+            assert_eq!(r.inner.calls.load(Ordering::SeqCst), 3);
+        }
+
+        #[tokio::test]
+        async fn not_retryable() {
+            let r = retried(2, Code::NotFound);
+            let e = r.all(7).await.err().unwrap();
+            assert_eq!(e.code(), Code::NotFound);
+            assert_eq!(r.inner.calls.load(Ordering::SeqCst), 1);
+        }
+    }
+}
@@ -0,0 +1,319 @@
+//! Enumerates buckets(files) by walking a directory
+//!
+//! The walk runs on a blocking thread; found files are sent in file name order.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use tokio_stream::wrappers::ReceiverStream;
+
+use tonic::Status;
+
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+use walkdir::{DirEntry, WalkDir};
+
+use crate::input::conv::lines::FsSource;
+use crate::input::source::Source;
+
+/// How to handle symbolic links found while walking
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SymlinkPolicy {
+    /// Ignores symbolic links(neither listed nor descended) under the root
+    #[default]
+    Skip,
+
+    /// Follows symbolic links(a loop is reported as an error)
+    Follow,
+}
+
+/// Files to enumerate
+#[derive(Debug, Clone)]
+pub struct WalkConfig {
+    /// The directory to walk
+    pub root: PathBuf,
+
+    /// Globs of paths(relative to the root) to list(empty: all files)
+    pub include: Vec<String>,
+
+    /// Globs of paths(relative to the root) to ignore; matched directories are not descended
+    pub exclude: Vec<String>,
+
+    /// Walks subdirectories if true
+    pub recursive: bool,
+
+    /// Symbolic links under the root; the root itself is always followed
+    pub symlinks: SymlinkPolicy,
+}
+
+impl WalkConfig {
+    /// Creates a config which lists all files just under the root
+    pub fn new<P>(root: P) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        Self {
+            root: root.into(),
+            include: vec![],
+            exclude: vec![],
+            recursive: false,
+            symlinks: SymlinkPolicy::default(),
+        }
+    }
+}
+
+/// A file found by the walker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkedFile {
+    /// The path of the file(the root joined with the relative path)
+    pub path: PathBuf,
+
+    /// The path relative to the root
+    pub rel: PathBuf,
+
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+fn globs(patterns: &[String]) -> Result<GlobSet, Status> {
+    let mut b = GlobSetBuilder::new();
+    for pat in patterns {
+        let g: Glob = GlobBuilder::new(pat)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| Status::invalid_argument(format!("invalid glob {pat}: {e}")))?;
+        b.add(g);
+    }
+    b.build()
+        .map_err(|e| Status::invalid_argument(format!("invalid globs: {e}")))
+}
+
+fn status(e: walkdir::Error) -> Status {
+    Status::internal(format!("unable to walk a directory: {e}"))
+}
+
+struct Filter {
+    include: GlobSet,
+    exclude: GlobSet,
+}
+
+impl Filter {
+    fn rel<'a>(root: &Path, ent: &'a DirEntry) -> &'a Path {
+        ent.path().strip_prefix(root).unwrap_or(ent.path())
+    }
+
+    fn keep(&self, root: &Path, ent: &DirEntry) -> bool {
+        let rel: &Path = Self::rel(root, ent);
+        ent.depth() == 0 || !self.exclude.is_match(rel)
+    }
+
+    fn list(&self, root: &Path, ent: &DirEntry) -> bool {
+        let rel: &Path = Self::rel(root, ent);
+        self.include.is_empty() || self.include.is_match(rel)
+    }
+}
+
+fn found(root: &Path, ent: DirEntry) -> Result<WalkedFile, Status> {
+    let meta = ent.metadata().map_err(status)?;
+    let modified: SystemTime = meta
+        .modified()
+        .map_err(|e| Status::internal(format!("unable to get mtime: {e}")))?;
+    let path: PathBuf = ent.into_path();
+    let rel: PathBuf = path.strip_prefix(root).unwrap_or(&path).to_path_buf();
+    Ok(WalkedFile {
+        path,
+        rel,
+        size: meta.len(),
+        modified,
+    })
+}
+
+fn walk(
+    cfg: &WalkConfig,
+    filter: &Filter,
+    tx: &tokio::sync::mpsc::Sender<Result<WalkedFile, Status>>,
+) {
+    let root: &Path = &cfg.root;
+    let follow: bool = cfg.symlinks == SymlinkPolicy::Follow;
+    let depth: usize = match cfg.recursive {
+        true => usize::MAX,
+        false => 1,
+    };
+    let entries = WalkDir::new(root)
+        .follow_links(follow)
+        .max_depth(depth)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|ent| {
+            let linked: bool = 0 < ent.depth() && ent.path_is_symlink();
+            (follow || !linked) && filter.keep(root, ent)
+        });
+    for rslt in entries {
+        let item: Result<WalkedFile, Status> = match rslt {
+            Err(e) => Err(status(e)),
+            Ok(ent) if !ent.file_type().is_file() => continue,
+            Ok(ent) if !filter.list(root, &ent) => continue,
+            Ok(ent) => found(root, ent),
+        };
+        if tx.blocking_send(item).is_err() {
+            return;
+        }
+    }
+}
+
+pub struct Walker {
+    cfg: Arc<WalkConfig>,
+    filter: Arc<Filter>,
+}
+
+#[tonic::async_trait]
+impl Source for Walker {
+    type Item = WalkedFile;
+    type All = ReceiverStream<Result<Self::Item, Status>>;
+
+    /// Gets all files found under the root
+    async fn all(&self) -> Result<Self::All, Status> {
+        let cfg = self.cfg.clone();
+        let filter = self.filter.clone();
+        let (tx, rx) = tokio::sync::mpsc::channel(1);
+        tokio::task::spawn_blocking(move || walk(&cfg, &filter, &tx));
+        Ok(ReceiverStream::new(rx))
+    }
+}
+
+/// Creates a [`Source`] which lists files under the root as buckets.
+///
+/// Fails if a glob is invalid.
+pub fn walker_new(cfg: WalkConfig) -> Result<impl Source<Item = WalkedFile>, Status> {
+    let filter = Filter {
+        include: globs(&cfg.include)?,
+        exclude: globs(&cfg.exclude)?,
+    };
+    Ok(Walker {
+        cfg: Arc::new(cfg),
+        filter: Arc::new(filter),
+    })
+}
+
+pub struct WalkedFsSource {}
+
+impl FsSource for WalkedFsSource {
+    type Bucket = WalkedFile;
+    type P = PathBuf;
+
+    fn bucket2path(&self, b: Self::Bucket) -> Result<Self::P, Status> {
+        Ok(b.path)
+    }
+}
+
+/// Creates a [`FsSource`] which opens files listed by [`walker_new`]
+pub fn walked_fs_source_new() -> impl FsSource<Bucket = WalkedFile, P = PathBuf> {
+    WalkedFsSource {}
+}
+
+#[cfg(test)]
+mod test_walk {
+    mod walker_new {
+        use std::path::PathBuf;
+
+        use futures::StreamExt;
+
+        use crate::input::conv::lines::bytes_src_new;
+        use crate::input::source::{BucketSource, Source};
+        use crate::input::walk::{walked_fs_source_new, walker_new, WalkConfig, WalkedFile};
+        use crate::testing::TempDir;
+
+        async fn rels(cfg: WalkConfig) -> Vec<PathBuf> {
+            let w = walker_new(cfg).unwrap();
+            let all = w.all().await.unwrap();
+            all.map(|r| r.unwrap().rel).collect().await
+        }
+
+        #[tokio::test]
+        async fn globs() {
+            let dir = TempDir::new("walk");
+            std::fs::create_dir_all(dir.join("sub/skip")).unwrap();
+            std::fs::write(dir.join("a.log"), b"a0\na1\n").unwrap();
+            std::fs::write(dir.join("b.txt"), b"b").unwrap();
+            std::fs::write(dir.join("sub/c.log"), b"c").unwrap();
+            std::fs::write(dir.join("sub/skip/d.log"), b"d").unwrap();
+
+            let flat = WalkConfig::new(dir.path());
+            assert_eq!(
+                rels(flat).await,
+                vec![PathBuf::from("a.log"), "b.txt".into()]
+            );
+
+            let mut deep = WalkConfig::new(dir.path());
+            deep.recursive = true;
+            deep.include = vec!["**/*.log".into()];
+            deep.exclude = vec!["sub/skip".into()];
+            assert_eq!(
+                rels(deep).await,
+                vec![PathBuf::from("a.log"), "sub/c.log".into()]
+            );
+
+            let mut top = WalkConfig::new(dir.path());
+            top.recursive = true;
+            top.include = vec!["*.log".into()];
+            let w = walker_new(top).unwrap();
+            let mut all = Box::pin(w.all().await.unwrap());
+            let f: WalkedFile = all.next().await.unwrap().unwrap();
+            assert!(all.next().await.is_none());
+            assert_eq!(f.size, 6);
+
+            let src = bytes_src_new(walked_fs_source_new());
+            let lines: Vec<_> = src.get_all_by_bucket(f).await.unwrap().collect().await;
+            assert_eq!(lines.len(), 2);
+
+            let mut bad = WalkConfig::new(dir.path());
+            bad.include = vec!["[".into()];
+            assert!(walker_new(bad).is_err());
+        }
+
+        #[cfg(unix)]
+        #[tokio::test]
+        async fn symlinks() {
+            use std::os::unix::fs::symlink;
+
+            use crate::input::walk::SymlinkPolicy;
+
+            let dir = TempDir::new("walk-links");
+            let root: PathBuf = dir.join("root");
+            std::fs::create_dir_all(root.join("real")).unwrap();
+            std::fs::write(root.join("real/a.log"), b"a").unwrap();
+            symlink(root.join("real/a.log"), root.join("b.log")).unwrap();
+            symlink(root.join("real"), root.join("linked")).unwrap();
+            symlink(&root, dir.join("alias")).unwrap();
+
+            let mut skip = WalkConfig::new(&root);
+            skip.recursive = true;
+            assert_eq!(skip.symlinks, SymlinkPolicy::Skip);
+            assert_eq!(rels(skip).await, vec![PathBuf::from("real/a.log")]);
+
+            let mut follow = WalkConfig::new(&root);
+            follow.recursive = true;
+            follow.symlinks = SymlinkPolicy::Follow;
+            assert_eq!(
+                rels(follow).await,
+                vec![
+                    PathBuf::from("b.log"),
+                    "linked/a.log".into(),
+                    "real/a.log".into()
+                ]
+            );
+
+            let mut alias = WalkConfig::new(dir.join("alias"));
+            alias.recursive = true;
+            assert_eq!(rels(alias).await, vec![PathBuf::from("real/a.log")]);
+
+            symlink(&root, root.join("real/loop")).unwrap();
+            let mut looped = WalkConfig::new(&root);
+            looped.recursive = true;
+            looped.symlinks = SymlinkPolicy::Follow;
+            let w = walker_new(looped).unwrap();
+            let got: Vec<_> = w.all().await.unwrap().collect().await;
+            assert!(got.iter().any(|r| r.is_err()));
+        }
+    }
+}
@@ -0,0 +1,25 @@
+//! Errors of items(rows) shared by sources and sinks
+
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::{Code, Status};
+
+/// The metadata key which may contain the key of a rejected item
+pub const KEY_METADATA: &str = "fs2db-key-bin";
+
+/// Creates a [`Status`] which keeps the key and the raw bytes of a bad item.
+///
+/// Sources can use this to report a bad item which can be recorded by a dead letter.
+pub fn item_status(code: Code, message: String, key: &[u8], raw: Vec<u8>) -> Status {
+    let mut m = MetadataMap::new();
+    m.insert_bin(KEY_METADATA, MetadataValue::from_bytes(key));
+    Status::with_details_and_metadata(code, message, raw.into(), m)
+}
+
+/// Gets the key kept by [`item_status`](empty if unknown)
+pub fn item_key(s: &Status) -> Vec<u8> {
+    s.metadata()
+        .get_bin(KEY_METADATA)
+        .and_then(|v| v.to_bytes().ok())
+        .map(|b| b.to_vec())
+        .unwrap_or_default()
+}
@@ -26,8 +26,22 @@ pub mod rpc {
 pub mod input;
 pub mod output;
 
+pub mod item;
+
+pub mod codec;
+
+pub mod checksum;
+
 pub mod conv;
 
+#[cfg(feature = "async_tokio")]
+pub mod retry;
+
+mod hex;
+
+#[cfg(test)]
+mod testing;
+
 pub use futures;
 pub use futures::StreamExt;
 pub use futures::TryStreamExt;
@@ -1,6 +1,14 @@
 pub mod upsert;
 
+pub mod dead;
+
 pub mod sync;
 
+#[cfg(all(feature = "grpc_tonic", feature = "target"))]
+pub mod rpc;
+
 #[cfg(feature = "async_tokio")]
 pub mod async_tokio;
+
+#[cfg(feature = "arrow")]
+pub mod arrow;
@@ -0,0 +1,236 @@
+//! Writes record batches as Arrow IPC files
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use futures::{Stream, StreamExt};
+
+use tonic::Status;
+
+use arrow_array::RecordBatch;
+use arrow_ipc::writer::{FileWriter, StreamWriter};
+
+use crate::output::upsert::Upsert;
+
+static TMP_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Format of Arrow IPC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpcFormat {
+    /// The file format(Feather v2) which supports random access
+    File,
+
+    /// The stream format
+    Stream,
+}
+
+fn status<E>(e: E) -> Status
+where
+    E: core::fmt::Display,
+{
+    Status::internal(format!("unable to write an arrow ipc file: {e}"))
+}
+
+/// A temporary file in the same directory as the target(to be renamed to the target)
+fn temp_path(target: &Path) -> PathBuf {
+    let name: String = target
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let seq: u64 = TMP_SEQ.fetch_add(1, Ordering::Relaxed);
+    target.with_file_name(format!(".{name}.{}-{seq}.tmp", std::process::id()))
+}
+
+/// Writes batches received until the channel closed(fails without a file if no batch received)
+fn write_all(
+    p: PathBuf,
+    fmt: IpcFormat,
+    rx: &mut tokio::sync::mpsc::Receiver<RecordBatch>,
+) -> Result<u64, Status> {
+    let first: RecordBatch = rx.blocking_recv().ok_or_else(|| {
+        Status::invalid_argument("no record batch to write(the schema is unknown)")
+    })?;
+    let f: File = File::create(p).map_err(status)?;
+    let bw = BufWriter::new(f);
+    let mut rows: u64 = 0;
+    match fmt {
+        IpcFormat::File => {
+            let mut w = FileWriter::try_new(bw, &first.schema()).map_err(status)?;
+            let mut next: Option<RecordBatch> = Some(first);
+            while let Some(b) = next {
+                w.write(&b).map_err(status)?;
+                rows += b.num_rows() as u64;
+                next = rx.blocking_recv();
+            }
+            w.finish().map_err(status)?;
+        }
+        IpcFormat::Stream => {
+            let mut w = StreamWriter::try_new(bw, &first.schema()).map_err(status)?;
+            let mut next: Option<RecordBatch> = Some(first);
+            while let Some(b) = next {
+                w.write(&b).map_err(status)?;
+                rows += b.num_rows() as u64;
+                next = rx.blocking_recv();
+            }
+            w.finish().map_err(status)?;
+        }
+    }
+    Ok(rows)
+}
+
+pub struct ArrowIpcSink {
+    fmt: IpcFormat,
+}
+
+#[tonic::async_trait]
+impl Upsert for ArrowIpcSink {
+    type Row = RecordBatch;
+    type Bucket = PathBuf;
+
+    /// Replaces the file with all batches and returns number of rows written.
+    ///
+    /// All batches must have the same schema.
+    /// Batches are written to a temporary file in the same directory which is renamed to the
+    /// target only if all batches are written; the target is kept as is on errors.
+    ///
+    /// Fails with [`Code::InvalidArgument`](tonic::Code::InvalidArgument) if there is no batch
+    /// (an IPC file needs a schema); the target is kept as is.
+    async fn upsert<S>(&self, bucket: Self::Bucket, rows: S) -> Result<u64, Status>
+    where
+        S: Stream<Item = Result<Self::Row, Status>> + Send,
+    {
+        let tmp: PathBuf = temp_path(&bucket);
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let fmt: IpcFormat = self.fmt;
+        let wpath: PathBuf = tmp.clone();
+        let writer = tokio::task::spawn_blocking(move || write_all(wpath, fmt, &mut rx));
+        let mut batches = Box::pin(rows);
+        let mut sent: Result<(), Status> = Ok(());
+        while let Some(rslt) = batches.next().await {
+            let b: RecordBatch = match rslt {
+                Ok(b) => b,
+                Err(e) => {
+                    sent = Err(e);
+                    break;
+                }
+            };
+            if tx.send(b).await.is_err() {
+                break;
+            }
+        }
+        drop(tx);
+        let written: Result<u64, Status> = writer.await.map_err(status).and_then(|r| r);
+        match (sent, written) {
+            (Ok(()), Ok(rows)) => match tokio::fs::rename(&tmp, &bucket).await {
+                Ok(()) => Ok(rows),
+                Err(e) => {
+                    let _ = tokio::fs::remove_file(&tmp).await;
+                    Err(status(e))
+                }
+            },
+            (sent, written) => {
+                let _ = tokio::fs::remove_file(&tmp).await;
+                sent.and(written).map(|_| 0)
+            }
+        }
+    }
+}
+
+/// Creates an [`Upsert`] which writes record batches to an Arrow IPC file per bucket(a path)
+pub fn arrow_ipc_sink_new(fmt: IpcFormat) -> impl Upsert<Row = RecordBatch, Bucket = PathBuf> {
+    ArrowIpcSink { fmt }
+}
+
+#[cfg(test)]
+mod test_arrow {
+    mod arrow_ipc_sink_new {
+        use std::path::Path;
+        use std::sync::Arc;
+
+        use futures::StreamExt;
+
+        use tonic::Status;
+
+        use arrow_array::{ArrayRef, Int64Array, RecordBatch, StringArray};
+
+        use crate::input::arrow::arrow_ipc_src_new;
+        use crate::input::source::BucketSource;
+        use crate::output::arrow::{arrow_ipc_sink_new, IpcFormat};
+        use crate::output::upsert::Upsert;
+        use crate::testing::{Dir, TempDir};
+
+        fn batch(ids: Vec<i64>) -> RecordBatch {
+            let names: Vec<String> = ids.iter().map(|i| format!("n{i}")).collect();
+            RecordBatch::try_from_iter(vec![
+                ("id", Arc::new(Int64Array::from(ids)) as ArrayRef),
+                ("name", Arc::new(StringArray::from(names)) as ArrayRef),
+            ])
+            .unwrap()
+        }
+
+        async fn roundtrip(dir: &Path, name: &'static str, fmt: IpcFormat) {
+            let sink = arrow_ipc_sink_new(fmt);
+            let batches = futures::stream::iter(vec![Ok(batch(vec![1, 2])), Ok(batch(vec![3]))]);
+            let rows: u64 = sink.upsert(dir.join(name), batches).await.unwrap();
+            assert_eq!(rows, 3);
+
+            let src = arrow_ipc_src_new(Dir {
+                dir: dir.to_path_buf(),
+            });
+            let all = src.get_all_by_bucket(name).await.unwrap();
+            let got: Vec<(usize, RecordBatch)> = all.map(|r| r.unwrap()).collect().await;
+            assert_eq!(got.len(), 2);
+            assert_eq!(got[1], (1, batch(vec![3])));
+        }
+
+        #[tokio::test]
+        async fn failed() {
+            let dir = TempDir::new("arrow-failed");
+            roundtrip(dir.path(), "b.arrow", IpcFormat::File).await;
+
+            let sink = arrow_ipc_sink_new(IpcFormat::File);
+            let batches = futures::stream::iter(vec![
+                Ok(batch(vec![4, 5])),
+                Err(Status::unavailable("input lost")),
+            ]);
+            let e: Status = sink.upsert(dir.join("b.arrow"), batches).await.unwrap_err();
+            assert_eq!(e.code(), tonic::Code::Unavailable);
+
+            let names: Vec<_> = std::fs::read_dir(dir.path())
+                .unwrap()
+                .map(|ent| ent.unwrap().file_name())
+                .collect();
+            assert_eq!(names, vec!["b.arrow"]);
+            let src = arrow_ipc_src_new(dir.dir());
+            let all = src.get_all_by_bucket("b.arrow").await.unwrap();
+            let got: Vec<(usize, RecordBatch)> = all.map(|r| r.unwrap()).collect().await;
+            assert_eq!(got[0], (0, batch(vec![1, 2])));
+        }
+
+        #[tokio::test]
+        async fn empty() {
+            let dir = TempDir::new("arrow-empty");
+            roundtrip(dir.path(), "b.arrow", IpcFormat::File).await;
+
+            let sink = arrow_ipc_sink_new(IpcFormat::File);
+            let batches = futures::stream::iter(Vec::<Result<RecordBatch, Status>>::new());
+            let e: Status = sink.upsert(dir.join("b.arrow"), batches).await.unwrap_err();
+            assert_eq!(e.code(), tonic::Code::InvalidArgument);
+
+            let names: Vec<_> = std::fs::read_dir(dir.path())
+                .unwrap()
+                .map(|ent| ent.unwrap().file_name())
+                .collect();
+            assert_eq!(names, vec!["b.arrow"]);
+        }
+
+        #[tokio::test]
+        async fn file_and_stream() {
+            let dir = TempDir::new("arrow");
+            roundtrip(dir.path(), "b.arrow", IpcFormat::File).await;
+            roundtrip(dir.path(), "b.arrows", IpcFormat::Stream).await;
+        }
+    }
+}
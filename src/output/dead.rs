@@ -0,0 +1,179 @@
+//! Dead letters(items rejected during a migration)
+
+use std::sync::Arc;
+
+use futures::Stream;
+use futures::StreamExt;
+
+use tokio::task::JoinHandle;
+
+use tokio_stream::wrappers::ReceiverStream;
+
+use tonic::Status;
+
+use crate::item::item_key;
+
+#[cfg(all(feature = "json", feature = "async_tokio"))]
+pub mod jsonl;
+
+/// An item which was rejected
+pub struct Rejected {
+    /// The bucket which returned the item
+    pub bucket: Vec<u8>,
+
+    /// The key of the item(empty if unknown)
+    pub key: Vec<u8>,
+
+    /// The raw bytes of the item(empty if unknown)
+    pub raw: Vec<u8>,
+
+    /// The reason of the rejection
+    pub status: Status,
+}
+
+impl Rejected {
+    /// Creates [`Rejected`] from an error returned by a bucket.
+    ///
+    /// The key is taken from the [`KEY_METADATA`](crate::item::KEY_METADATA)
+    /// and the raw bytes from the details.
+    pub fn from_status(bucket: Vec<u8>, status: Status) -> Self {
+        let key: Vec<u8> = item_key(&status);
+        let raw: Vec<u8> = status.details().to_vec();
+        Self {
+            bucket,
+            key,
+            raw,
+            status,
+        }
+    }
+}
+
+/// Records rejected items
+#[tonic::async_trait]
+pub trait DeadLetter: Send + Sync + 'static {
+    async fn reject(&self, r: Rejected) -> Result<(), Status>;
+}
+
+/// Discards rejected items
+pub struct Discard {}
+
+#[tonic::async_trait]
+impl DeadLetter for Discard {
+    async fn reject(&self, _r: Rejected) -> Result<(), Status> {
+        Ok(())
+    }
+}
+
+/// Passes items and sends rejected items to the [`DeadLetter`].
+///
+/// An item is `Ok(Err(status))` if it was rejected in-band(the stream continues);
+/// the status should be created by [`item_status`](crate::item::item_status) to keep the key.
+///
+/// An error of the stream(e.g, the RPC failed mid-stream) ends the items;
+/// the handle returns the error instead of the count so that a truncated stream is never
+/// reported as a success.
+///
+/// The handle returns the number of rejected items or the error of the stream/[`DeadLetter`].
+/// The stream ends early if the [`DeadLetter`] fails.
+pub fn reject_errs<S, T, D>(
+    items: S,
+    bucket: Vec<u8>,
+    dl: Arc<D>,
+) -> (ReceiverStream<T>, JoinHandle<Result<u64, Status>>)
+where
+    S: Stream<Item = Result<Result<T, Status>, Status>> + Send + Unpin + 'static,
+    T: Send + 'static,
+    D: DeadLetter,
+{
+    let (tx, rx) = tokio::sync::mpsc::channel(1);
+    let handle = tokio::spawn(async move {
+        let mut items = items;
+        let mut rejected: u64 = 0;
+        while let Some(rslt) = items.next().await {
+            match rslt? {
+                Ok(item) => {
+                    if tx.send(item).await.is_err() {
+                        break;
+                    }
+                }
+                Err(status) => {
+                    dl.reject(Rejected::from_status(bucket.clone(), status))
+                        .await?;
+                    rejected += 1;
+                }
+            }
+        }
+        Ok(rejected)
+    });
+    (ReceiverStream::new(rx), handle)
+}
+
+/// Waits the handle returned by [`reject_errs`]
+pub async fn rejected_count(h: JoinHandle<Result<u64, Status>>) -> Result<u64, Status> {
+    h.await
+        .map_err(|e| Status::internal(format!("unable to reject items: {e}")))?
+}
+
+#[cfg(test)]
+mod test_dead {
+    mod reject_errs {
+        use std::sync::Arc;
+
+        use futures::StreamExt;
+
+        use tonic::{Code, Status};
+
+        use crate::item::item_status;
+        use crate::output::dead::{reject_errs, rejected_count};
+        use crate::testing::MemDeadLetter;
+
+        #[tokio::test]
+        async fn rejected() {
+            let items = futures::stream::iter(vec![
+                Ok(Ok(1)),
+                Ok(Err(item_status(
+                    Code::InvalidArgument,
+                    "bad row".into(),
+                    b"k2",
+                    b"raw2".to_vec(),
+                ))),
+                Ok(Ok(3)),
+                Ok(Err(Status::internal("no key"))),
+                Ok(Ok(5)),
+            ]);
+            let dl = Arc::new(MemDeadLetter::default());
+            let (passed, h) = reject_errs(items, b"bkt".to_vec(), dl.clone());
+            let ok: Vec<i32> = passed.collect().await;
+            assert_eq!(ok, vec![1, 3, 5]);
+            assert_eq!(rejected_count(h).await.unwrap(), 2);
+
+            let v = dl.rejected.lock().unwrap();
+            assert_eq!(
+                v[0],
+                (
+                    b"bkt".to_vec(),
+                    b"k2".to_vec(),
+                    b"raw2".to_vec(),
+                    Code::InvalidArgument
+                )
+            );
+            assert_eq!(v[1], (b"bkt".to_vec(), vec![], vec![], Code::Internal));
+        }
+
+        #[tokio::test]
+        async fn truncated() {
+            let items = futures::stream::iter(vec![
+                Ok(Ok(1)),
+                Err(Status::unavailable("connection lost")),
+                Ok(Ok(3)),
+            ]);
+            let dl = Arc::new(MemDeadLetter::default());
+            let (passed, h) = reject_errs(items, b"bkt".to_vec(), dl.clone());
+            let ok: Vec<i32> = passed.collect().await;
+            assert_eq!(ok, vec![1]);
+            let e: Status = rejected_count(h).await.unwrap_err();
+            assert_eq!(e.code(), Code::Unavailable);
+            assert!(dl.rejected.lock().unwrap().is_empty());
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Writes rejected items as JSON lines
+
+use std::path::Path;
+
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+use tonic::Status;
+
+use crate::hex;
+use crate::output::dead::{DeadLetter, Rejected};
+
+/// Appends a JSON object per rejected item to a file.
+///
+/// ## Example
+///
+/// ```json
+/// {"bucket":"6964","key":"6b32","raw":"","code":3,"status":"InvalidArgument","message":"bad row"}
+/// ```
+///
+/// bucket, key and raw are hex encoded.
+pub struct JsonLinesDeadLetter {
+    file: Mutex<File>,
+}
+
+impl JsonLinesDeadLetter {
+    fn to_line(r: &Rejected) -> Result<Vec<u8>, Status> {
+        let obj = serde_json::json!({
+            "bucket": hex::encode(&r.bucket),
+            "key": hex::encode(&r.key),
+            "raw": hex::encode(&r.raw),
+            "code": r.status.code() as i32,
+            "status": format!("{:?}", r.status.code()),
+            "message": r.status.message(),
+        });
+        let mut line: Vec<u8> = serde_json::to_vec(&obj)
+            .map_err(|e| Status::internal(format!("unable to serialize a rejected item: {e}")))?;
+        line.push(b'\n');
+        Ok(line)
+    }
+}
+
+#[tonic::async_trait]
+impl DeadLetter for JsonLinesDeadLetter {
+    async fn reject(&self, r: Rejected) -> Result<(), Status> {
+        let line: Vec<u8> = Self::to_line(&r)?;
+        let mut f = self.file.lock().await;
+        f.write_all(&line)
+            .await
+            .map_err(|e| Status::internal(format!("unable to write a rejected item: {e}")))?;
+        f.flush()
+            .await
+            .map_err(|e| Status::internal(format!("unable to flush rejected items: {e}")))
+    }
+}
+
+/// Creates a [`DeadLetter`] which appends rejected items to the file
+pub async fn jsonl_dead_letter_new<P>(p: P) -> Result<impl DeadLetter, Status>
+where
+    P: AsRef<Path>,
+{
+    let f: File = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(p)
+        .await
+        .map_err(|e| Status::internal(format!("unable to open a dead letter file: {e}")))?;
+    Ok(JsonLinesDeadLetter {
+        file: Mutex::new(f),
+    })
+}
+
+#[cfg(test)]
+mod test_jsonl {
+    mod jsonl_dead_letter_new {
+        use std::path::PathBuf;
+
+        use serde_json::Value;
+
+        use tonic::{Code, Status};
+
+        use crate::item::item_status;
+        use crate::output::dead::jsonl::jsonl_dead_letter_new;
+        use crate::output::dead::{DeadLetter, Rejected};
+        use crate::testing::TempDir;
+
+        fn rejected(bucket: &[u8], key: &[u8], message: &str) -> Rejected {
+            let s: Status = item_status(Code::InvalidArgument, message.into(), key, b"r".to_vec());
+            Rejected::from_status(bucket.to_vec(), s)
+        }
+
+        fn lines(p: &PathBuf) -> Vec<Value> {
+            let s: String = std::fs::read_to_string(p).unwrap();
+            s.lines()
+                .map(|l| serde_json::from_str(l).unwrap())
+                .collect()
+        }
+
+        #[tokio::test]
+        async fn appended() {
+            let dir = TempDir::new("jsonl");
+            let p: PathBuf = dir.join("dead.jsonl");
+
+            let dl = jsonl_dead_letter_new(&p).await.unwrap();
+            dl.reject(rejected(b"id", b"k2", "bad row")).await.unwrap();
+            dl.reject(rejected(b"id", b"k3", "too long")).await.unwrap();
+            drop(dl);
+
+            let dl = jsonl_dead_letter_new(&p).await.unwrap();
+            dl.reject(rejected(b"other", b"k1", "bad row"))
+                .await
+                .unwrap();
+            drop(dl);
+
+            let got: Vec<Value> = lines(&p);
+            assert_eq!(got.len(), 3);
+            assert_eq!(got[0]["bucket"], "6964");
+            assert_eq!(got[0]["key"], "6b32");
+            assert_eq!(got[0]["raw"], "72");
+            assert_eq!(got[0]["code"], Code::InvalidArgument as i32);
+            assert_eq!(got[0]["status"], "InvalidArgument");
+            assert_eq!(got[0]["message"], "bad row");
+            assert_eq!(got[1]["key"], "6b33");
+            assert_eq!(got[1]["message"], "too long");
+            assert_eq!(got[2]["bucket"], "6f74686572");
+            assert_eq!(got[2]["key"], "6b31");
+        }
+    }
+}
@@ -0,0 +1,3 @@
+//! Target services
+
+pub mod server;
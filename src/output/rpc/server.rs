@@ -0,0 +1,227 @@
+//! Serves an [`Upsert`] as a target upsert service
+
+use std::sync::Arc;
+
+use futures::Stream;
+use futures::StreamExt;
+
+use tonic::codec::Streaming;
+use tonic::{Request, Response, Status};
+
+use crate::codec::{Decoder, PairDecoder};
+use crate::output::upsert::Upsert;
+
+use crate::rpc::fs2db::proto::target;
+use target::v1::upsert_batch_service_server::UpsertBatchService;
+use target::v1::upsert_service_server::UpsertService;
+use target::v1::upst_svc::{BatchRequest, ManyRequest, ManyResponse};
+use target::v1::OutputBucket;
+
+pub struct UpsertSvc<U, D, R> {
+    ups: U,
+    bdec: D,
+    rdec: Arc<R>,
+}
+
+impl<U, D, R> UpsertSvc<U, D, R>
+where
+    U: Upsert + Send + Sync + 'static,
+    U::Bucket: Send,
+    D: Decoder<T = U::Bucket>,
+    R: PairDecoder<T = U::Row>,
+{
+    /// Upserts all rows using the bucket of the first request.
+    ///
+    /// Requests with a different bucket will be rejected.
+    pub async fn upsert_requests<S>(&self, reqs: S) -> Result<u64, Status>
+    where
+        S: Stream<Item = Result<ManyRequest, Status>> + Send + Unpin + 'static,
+    {
+        let mut reqs = reqs;
+        let first: ManyRequest = match reqs.next().await {
+            None => return Ok(0),
+            Some(rslt) => rslt?,
+        };
+        let bkt: OutputBucket = first
+            .bkt
+            .clone()
+            .ok_or_else(|| Status::invalid_argument("output bucket missing"))?;
+        let decoded: U::Bucket = self.bdec.decode(bkt.bucket.clone())?;
+        let rdec: Arc<R> = self.rdec.clone();
+        let all = futures::stream::once(async { Ok(first) }).chain(reqs);
+        let rows = all.map(move |rslt| {
+            rslt.and_then(|req: ManyRequest| {
+                let same: bool = req.bkt.as_ref() == Some(&bkt);
+                same.then_some(req)
+                    .ok_or_else(|| Status::invalid_argument("output bucket changed"))
+            })
+            .and_then(|req| rdec.decode(req.key, req.val, req.check))
+        });
+        self.ups.upsert(decoded, rows).await
+    }
+}
+
+#[tonic::async_trait]
+impl<U, D, R> UpsertService for UpsertSvc<U, D, R>
+where
+    U: Upsert + Send + Sync + 'static,
+    U::Bucket: Send,
+    D: Decoder<T = U::Bucket>,
+    R: PairDecoder<T = U::Row>,
+{
+    async fn many(
+        &self,
+        req: Request<Streaming<ManyRequest>>,
+    ) -> Result<Response<ManyResponse>, Status> {
+        let reqs: Streaming<ManyRequest> = req.into_inner();
+        let upserted: u64 = self.upsert_requests(reqs).await?;
+        Ok(Response::new(ManyResponse { upserted }))
+    }
+}
+
+#[tonic::async_trait]
+impl<U, D, R> UpsertBatchService for UpsertSvc<U, D, R>
+where
+    U: Upsert + Send + Sync + 'static,
+    U::Bucket: Send,
+    D: Decoder<T = U::Bucket>,
+    R: PairDecoder<T = U::Row>,
+{
+    async fn many(
+        &self,
+        req: Request<Streaming<BatchRequest>>,
+    ) -> Result<Response<ManyResponse>, Status> {
+        let batches: Streaming<BatchRequest> = req.into_inner();
+        let reqs = batches.flat_map(|rslt| {
+            let flat: Vec<Result<ManyRequest, Status>> = match rslt {
+                Err(e) => vec![Err(e)],
+                Ok(batch) => {
+                    let bkt: Option<OutputBucket> = batch.bkt;
+                    batch
+                        .pairs
+                        .into_iter()
+                        .map(|p| {
+                            Ok(ManyRequest {
+                                bkt: bkt.clone(),
+                                key: p.key,
+                                val: p.val,
+                                check: p.check,
+                            })
+                        })
+                        .collect()
+                }
+            };
+            futures::stream::iter(flat)
+        });
+        let upserted: u64 = self.upsert_requests(reqs).await?;
+        Ok(Response::new(ManyResponse { upserted }))
+    }
+}
+
+/// Creates a target upsert service from an [`Upsert`] and codecs.
+///
+/// The service implements both UpsertService and UpsertBatchService;
+/// wrap it with [`Arc`] to serve both(e.g, `UpsertServiceServer::from_arc`).
+///
+/// ## Arguments
+/// - ups: An [`Upsert`] which saves rows(e.g, a database writer)
+/// - bdec: A [`Decoder`] which decodes an output bucket received over gRPC
+/// - rdec: A [`PairDecoder`] which decodes a row from a key/val pair
+pub fn upsert_svc_new<U, D, R>(ups: U, bdec: D, rdec: R) -> UpsertSvc<U, D, R>
+where
+    U: Upsert + Send + Sync + 'static,
+    U::Bucket: Send,
+    D: Decoder<T = U::Bucket>,
+    R: PairDecoder<T = U::Row>,
+{
+    UpsertSvc {
+        ups,
+        bdec,
+        rdec: Arc::new(rdec),
+    }
+}
+
+#[cfg(test)]
+mod test_server {
+    mod upsert_svc_new {
+        use std::sync::Mutex;
+
+        use futures::{Stream, TryStreamExt};
+
+        use tonic::Status;
+
+        use crate::codec::{Pair, Raw, Utf8};
+        use crate::output::rpc::server::upsert_svc_new;
+        use crate::output::upsert::Upsert;
+
+        use crate::rpc::fs2db::proto::target;
+        use target::v1::upst_svc::ManyRequest;
+        use target::v1::OutputBucket;
+
+        #[derive(Default)]
+        struct MemUpsert {
+            rows: Mutex<Vec<(String, String, Vec<u8>)>>,
+        }
+
+        #[tonic::async_trait]
+        impl Upsert for MemUpsert {
+            type Row = (String, Vec<u8>);
+            type Bucket = String;
+
+            async fn upsert<S>(&self, bucket: Self::Bucket, rows: S) -> Result<u64, Status>
+            where
+                S: Stream<Item = Result<Self::Row, Status>> + Send,
+            {
+                let all: Vec<Self::Row> = Box::pin(rows).try_collect().await?;
+                let mut m = self.rows.lock().unwrap();
+                for (k, v) in all {
+                    m.push((bucket.clone(), k, v));
+                }
+                Ok(m.len() as u64)
+            }
+        }
+
+        fn req(bkt: &[u8], key: &[u8]) -> Result<ManyRequest, Status> {
+            Ok(ManyRequest {
+                bkt: Some(OutputBucket {
+                    bucket: bkt.to_vec(),
+                }),
+                key: key.to_vec(),
+                val: vec![42],
+                check: vec![],
+            })
+        }
+
+        #[tokio::test]
+        async fn upserted() {
+            let svc = upsert_svc_new(
+                MemUpsert::default(),
+                Utf8 {},
+                Pair {
+                    key: Utf8 {},
+                    val: Raw {},
+                },
+            );
+            let reqs = futures::stream::iter(vec![req(b"tab", b"k1"), req(b"tab", b"k2")]);
+            let cnt: u64 = svc.upsert_requests(reqs).await.unwrap();
+            assert_eq!(cnt, 2);
+            let rows = svc.ups.rows.lock().unwrap();
+            assert_eq!(rows[1], ("tab".into(), "k2".into(), vec![42]));
+        }
+
+        #[tokio::test]
+        async fn bucket_changed() {
+            let svc = upsert_svc_new(
+                MemUpsert::default(),
+                Utf8 {},
+                Pair {
+                    key: Utf8 {},
+                    val: Raw {},
+                },
+            );
+            let reqs = futures::stream::iter(vec![req(b"tab", b"k1"), req(b"other", b"k2")]);
+            let rslt = svc.upsert_requests(reqs).await;
+            assert!(rslt.is_err());
+        }
+    }
+}
@@ -9,5 +9,5 @@ pub trait Upsert {
 
     async fn upsert<S>(&self, bucket: Self::Bucket, rows: S) -> Result<u64, Status>
     where
-        S: Stream<Item = Result<Self::Row, Status>>;
+        S: Stream<Item = Result<Self::Row, Status>> + Send;
 }
@@ -0,0 +1,154 @@
+//! Retries transient gRPC failures with exponential backoff
+//!
+//! Retries are applied per call(e.g, [`retry`]) instead of a tower layer on the channel;
+//! streaming requests are consumed while sent and can not be replayed by a generic layer.
+//! Upserts are idempotent, so a whole bucket can be migrated again instead.
+
+use core::future::Future;
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use tonic::{Code, Status};
+
+/// Decides whether and when a failed call is retried
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Max number of calls including the first one(at least 1)
+    pub max_attempts: u32,
+
+    /// The base delay before the first retry
+    pub initial: Duration,
+
+    /// The upper bound of delays
+    pub max_delay: Duration,
+
+    /// Status codes which will be retried
+    pub retryable: Vec<Code>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            retryable: vec![
+                Code::Unavailable,
+                Code::DeadlineExceeded,
+                Code::ResourceExhausted,
+                Code::Aborted,
+            ],
+        }
+    }
+}
+
+fn jitter() -> f64 {
+    let mut h = RandomState::new().build_hasher();
+    h.write_u8(0);
+    (h.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+impl RetryPolicy {
+    /// Checks if the status can be retried
+    pub fn is_retryable(&self, s: &Status) -> bool {
+        self.retryable.contains(&s.code())
+    }
+
+    /// Computes a delay before the retry(full jitter: random in 0..=min(max, initial * 2^retried))
+    pub fn delay(&self, retried: u32) -> Duration {
+        let exp: Duration = self
+            .initial
+            .saturating_mul(1u32.checked_shl(retried).unwrap_or(u32::MAX));
+        exp.min(self.max_delay).mul_f64(jitter())
+    }
+}
+
+/// Calls the function until it succeeds, fails with a non retryable status or attempts exhausted.
+///
+/// ## Arguments
+/// - p: The [`RetryPolicy`]
+/// - f: Creates a new call for each attempt
+pub async fn retry<F, Fut, T>(p: &RetryPolicy, f: F) -> Result<T, Status>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Status>>,
+{
+    let mut f = f;
+    let mut retried: u32 = 0;
+    loop {
+        match f().await {
+            Ok(t) => return Ok(t),
+            Err(e) if p.is_retryable(&e) && retried + 1 < p.max_attempts => {
+                tokio::time::sleep(p.delay(retried)).await;
+                retried += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_retry {
+    mod retry {
+        use std::time::Duration;
+
+        use tonic::{Code, Status};
+
+        use crate::retry::{retry, RetryPolicy};
+
+        fn policy() -> RetryPolicy {
+            RetryPolicy {
+                max_attempts: 3,
+                initial: Duration::from_millis(1),
+                max_delay: Duration::from_millis(2),
+                ..Default::default()
+            }
+        }
+
+        async fn failing(code: Code, fails: u32) -> (Result<u32, Status>, u32) {
+            let mut calls: u32 = 0;
+            let rslt = retry(&policy(), || {
+                calls += 1;
+                let c: u32 = calls;
+                async move {
+                    match c <= fails {
+                        true => Err(Status::new(code, "failed")),
+                        false => Ok(c),
+                    }
+                }
+            })
+            .await;
+            (rslt, calls)
+        }
+
+        #[tokio::test]
+        async fn recovered() {
+            let (rslt, calls) = failing(Code::Unavailable, 2).await;
+            assert_eq!(rslt.unwrap(), 3);
+            assert_eq!(calls, 3);
+        }
+
+        #[tokio::test]
+        async fn exhausted() {
+            let (rslt, calls) = failing(Code::Unavailable, 5).await;
+            assert_eq!(rslt.unwrap_err().code(), Code::Unavailable);
+            assert_eq!(calls, 3);
+        }
+
+        #[tokio::test]
+        async fn not_retryable() {
+            let (rslt, calls) = failing(Code::InvalidArgument, 5).await;
+            assert_eq!(rslt.unwrap_err().code(), Code::InvalidArgument);
+            assert_eq!(calls, 1);
+        }
+
+        #[test]
+        fn delay_bounded() {
+            let p = policy();
+            assert!(p.delay(0) <= Duration::from_millis(1));
+            assert!(p.delay(40) <= Duration::from_millis(2));
+        }
+    }
+}
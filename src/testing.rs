@@ -0,0 +1,391 @@
+//! Fixtures shared by tests
+
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use tonic::{Code, Status};
+
+use crate::conv::checkpoint::Checkpoint;
+use crate::input::conv::lines::{FsSource, ReadSource};
+use crate::output::dead::{DeadLetter, Rejected};
+
+/// A directory under the system temp dir which is removed on drop(even if a test panics)
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Creates `fs2db-test-{name}-{pid}`; the name must be unique among tests
+    pub fn new(name: &str) -> Self {
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("fs2db-test-{name}-{}", std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn join<P>(&self, p: P) -> PathBuf
+    where
+        P: AsRef<Path>,
+    {
+        self.path.join(p)
+    }
+
+    /// Creates a [`Dir`] which resolves buckets under this directory
+    pub fn dir(&self) -> Dir {
+        Dir {
+            dir: self.path.clone(),
+        }
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A [`ReadSource`] which reads a bucket itself
+pub struct Mem<B> {
+    bucket: PhantomData<fn() -> B>,
+}
+
+/// Creates a [`Mem`]
+pub fn mem_new<B>() -> Mem<B> {
+    Mem {
+        bucket: PhantomData,
+    }
+}
+
+#[tonic::async_trait]
+impl<B> ReadSource for Mem<B>
+where
+    B: AsRef<[u8]> + Unpin + Send + Sync + 'static,
+{
+    type Bucket = B;
+    type R = Cursor<B>;
+
+    async fn get_src_read_by_bucket(&self, b: Self::Bucket) -> Result<Self::R, Status> {
+        Ok(Cursor::new(b))
+    }
+}
+
+/// The content of testdata/multi-member.txt.*
+pub const MEMBERS: &[u8] = b"member1-line1\nmember1-line2\nmember2-line1\nmember3-line1\n";
+
+/// Reads all bytes of a bucket
+pub async fn read_all<R>(rsrc: &R, b: R::Bucket) -> Result<Vec<u8>, Status>
+where
+    R: ReadSource,
+{
+    use tokio::io::AsyncReadExt;
+    let mut r: R::R = rsrc.get_src_read_by_bucket(b).await?;
+    let mut buf: Vec<u8> = vec![];
+    r.read_to_end(&mut buf).await.unwrap();
+    Ok(buf)
+}
+
+/// A [`FsSource`] which resolves buckets(file names) under the directory
+pub struct Dir {
+    pub dir: PathBuf,
+}
+
+impl FsSource for Dir {
+    type Bucket = &'static str;
+    type P = PathBuf;
+
+    fn bucket2path(&self, b: Self::Bucket) -> Result<Self::P, Status> {
+        Ok(self.dir.join(b))
+    }
+}
+
+/// bucket, key, raw bytes and the code of a rejected item
+pub type Entry = (Vec<u8>, Vec<u8>, Vec<u8>, Code);
+
+/// A [`DeadLetter`] which keeps rejected items
+#[derive(Default)]
+pub struct MemDeadLetter {
+    pub rejected: Mutex<Vec<Entry>>,
+}
+
+#[tonic::async_trait]
+impl DeadLetter for MemDeadLetter {
+    async fn reject(&self, r: Rejected) -> Result<(), Status> {
+        let mut v = self.rejected.lock().unwrap();
+        v.push((r.bucket, r.key, r.raw, r.status.code()));
+        Ok(())
+    }
+}
+
+/// (input bucket, output bucket) -> the last key
+pub type Keys = BTreeMap<(Vec<u8>, Vec<u8>), Vec<u8>>;
+
+/// A [`Checkpoint`] which keeps keys in memory
+#[derive(Default)]
+pub struct MemCheckpoint {
+    pub keys: Mutex<Keys>,
+}
+
+#[tonic::async_trait]
+impl Checkpoint for MemCheckpoint {
+    async fn last_key(&self, ibkt: &[u8], obkt: &[u8]) -> Result<Option<Vec<u8>>, Status> {
+        let m = self.keys.lock().unwrap();
+        Ok(m.get(&(ibkt.to_vec(), obkt.to_vec())).cloned())
+    }
+
+    async fn save(&self, ibkt: &[u8], obkt: &[u8], key: &[u8]) -> Result<(), Status> {
+        let mut m = self.keys.lock().unwrap();
+        m.insert((ibkt.to_vec(), obkt.to_vec()), key.to_vec());
+        Ok(())
+    }
+
+    async fn clear(&self, ibkt: &[u8], obkt: &[u8]) -> Result<(), Status> {
+        let mut m = self.keys.lock().unwrap();
+        m.remove(&(ibkt.to_vec(), obkt.to_vec()));
+        Ok(())
+    }
+}
+
+/// Services served over a local TCP port
+#[cfg(all(feature = "grpc_tonic", feature = "source", feature = "target"))]
+pub mod rpc {
+    use std::collections::{BTreeMap, VecDeque};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use futures::StreamExt;
+
+    use tokio_stream::wrappers::ReceiverStream;
+
+    use tonic::codec::Streaming;
+    use tonic::transport::server::Router;
+    use tonic::transport::Channel;
+    use tonic::{Request, Response, Status};
+
+    use crate::rpc::fs2db::proto::source;
+    use source::v1::drop_service_server::DropService;
+    use source::v1::drop_svc::{CheckedRequest, CheckedResponse};
+    use source::v1::select_service_server::SelectService;
+    use source::v1::{AllRequest, AllResponse};
+
+    use crate::rpc::fs2db::proto::target;
+    use target::v1::sel_svc::{AllRequest as TgtAll, AllResponse as TgtPair};
+    use target::v1::select_service_server::SelectService as TgtSelect;
+    use target::v1::upsert_batch_service_server::UpsertBatchService;
+    use target::v1::upsert_service_server::UpsertService;
+    use target::v1::upst_svc::{BatchRequest, ManyRequest, ManyResponse};
+
+    /// Serves the router on a local port and connects to it
+    pub async fn serve(router: Router) -> Channel {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming = futures::stream::unfold(listener, |l| async move {
+            let accepted = l.accept().await.map(|pair| pair.0);
+            Some((accepted, l))
+        });
+        tokio::spawn(router.serve_with_incoming(incoming));
+        Channel::from_shared(format!("http://{addr}"))
+            .unwrap()
+            .connect()
+            .await
+            .unwrap()
+    }
+
+    /// Creates a pair
+    pub fn pair(key: &[u8], val: &[u8]) -> AllResponse {
+        AllResponse {
+            key: key.to_vec(),
+            val: val.to_vec(),
+            rejected: None,
+        }
+    }
+
+    /// A source select service which sends the same pairs(sorted by keys) for any bucket
+    #[derive(Default)]
+    pub struct MemSource {
+        pub pairs: Vec<AllResponse>,
+
+        /// The n-th call sends the error after sending the number of pairs
+        pub fails: Mutex<VecDeque<Option<(usize, Status)>>>,
+
+        /// start_after of each call
+        pub calls: Mutex<Vec<Vec<u8>>>,
+
+        /// Waits the duration before sending the n-th pair
+        pub pause: Option<(usize, Duration)>,
+
+        /// Buckets dropped
+        pub dropped: Mutex<Vec<Vec<u8>>>,
+
+        /// Number of drop calls
+        pub checked: AtomicUsize,
+
+        /// Number of streams being sent
+        pub active: Arc<AtomicUsize>,
+
+        /// Max number of streams sent at the same time
+        pub max_active: AtomicUsize,
+    }
+
+    impl MemSource {
+        pub fn new(pairs: Vec<AllResponse>) -> Self {
+            Self {
+                pairs,
+                ..Default::default()
+            }
+        }
+    }
+
+    #[tonic::async_trait]
+    impl SelectService for MemSource {
+        type AllStream = ReceiverStream<Result<AllResponse, Status>>;
+
+        async fn all(&self, req: Request<AllRequest>) -> Result<Response<Self::AllStream>, Status> {
+            let start_after: Vec<u8> = req.into_inner().start_after;
+            self.calls.lock().unwrap().push(start_after.clone());
+            let fail: Option<(usize, Status)> = self.fails.lock().unwrap().pop_front().flatten();
+            let mut items: Vec<Result<AllResponse, Status>> = self
+                .pairs
+                .iter()
+                .filter(|a| start_after.is_empty() || start_after < a.key)
+                .cloned()
+                .map(Ok)
+                .collect();
+            if let Some((cnt, e)) = fail {
+                items.truncate(cnt);
+                items.push(Err(e));
+            }
+            let pause: Option<(usize, Duration)> = self.pause;
+            let active: Arc<AtomicUsize> = self.active.clone();
+            let cnt: usize = 1 + active.fetch_add(1, Ordering::SeqCst);
+            self.max_active.fetch_max(cnt, Ordering::SeqCst);
+            let (tx, rx) = tokio::sync::mpsc::channel(1);
+            tokio::spawn(async move {
+                for (ix, item) in items.into_iter().enumerate() {
+                    if let Some((_, d)) = pause.filter(|p| p.0 == ix) {
+                        tokio::time::sleep(d).await;
+                    }
+                    if tx.send(item).await.is_err() {
+                        break;
+                    }
+                }
+                active.fetch_sub(1, Ordering::SeqCst);
+            });
+            Ok(Response::new(ReceiverStream::new(rx)))
+        }
+    }
+
+    #[tonic::async_trait]
+    impl DropService for MemSource {
+        async fn checked(
+            &self,
+            req: Request<Streaming<CheckedRequest>>,
+        ) -> Result<Response<CheckedResponse>, Status> {
+            self.checked.fetch_add(1, Ordering::SeqCst);
+            let reqs: Vec<Result<CheckedRequest, Status>> = req.into_inner().collect().await;
+            let checked: Vec<CheckedRequest> = reqs.into_iter().collect::<Result<_, _>>()?;
+            if let Some(b) = checked.first().and_then(|c| c.bkt.clone()) {
+                self.dropped.lock().unwrap().push(b.bucket);
+            }
+            Ok(Response::new(CheckedResponse {
+                keys_count: checked.len() as u64,
+            }))
+        }
+    }
+
+    /// key -> (val, check)
+    pub type Rows = BTreeMap<Vec<u8>, (Vec<u8>, Vec<u8>)>;
+
+    /// A target upsert service which keeps rows
+    #[derive(Default)]
+    pub struct MemTarget {
+        pub rows: Mutex<Rows>,
+
+        /// Number of rows of each call(or each batch)
+        pub calls: Mutex<Vec<usize>>,
+    }
+
+    impl MemTarget {
+        /// Upserts rows and returns the number of rows
+        pub fn upsert<I>(&self, rows: I) -> u64
+        where
+            I: IntoIterator<Item = (Vec<u8>, Vec<u8>, Vec<u8>)>,
+        {
+            let mut m = self.rows.lock().unwrap();
+            let cnt: usize = rows.into_iter().fold(0, |tot, row| {
+                let (key, val, check) = row;
+                m.insert(key, (val, check));
+                tot + 1
+            });
+            self.calls.lock().unwrap().push(cnt);
+            cnt as u64
+        }
+
+        pub fn keys(&self) -> Vec<Vec<u8>> {
+            self.rows.lock().unwrap().keys().cloned().collect()
+        }
+    }
+
+    #[tonic::async_trait]
+    impl UpsertService for MemTarget {
+        async fn many(
+            &self,
+            req: Request<Streaming<ManyRequest>>,
+        ) -> Result<Response<ManyResponse>, Status> {
+            let reqs: Vec<Result<ManyRequest, Status>> = req.into_inner().collect().await;
+            let rows: Vec<ManyRequest> = reqs.into_iter().collect::<Result<_, _>>()?;
+            let upserted: u64 = self.upsert(rows.into_iter().map(|r| (r.key, r.val, r.check)));
+            Ok(Response::new(ManyResponse { upserted }))
+        }
+    }
+
+    #[tonic::async_trait]
+    impl UpsertBatchService for MemTarget {
+        async fn many(
+            &self,
+            req: Request<Streaming<BatchRequest>>,
+        ) -> Result<Response<ManyResponse>, Status> {
+            let mut reqs = req.into_inner();
+            let mut upserted: u64 = 0;
+            while let Some(rslt) = reqs.next().await {
+                let batch: BatchRequest = rslt?;
+                upserted += self.upsert(batch.pairs.into_iter().map(|p| (p.key, p.val, p.check)));
+            }
+            Ok(Response::new(ManyResponse { upserted }))
+        }
+    }
+
+    #[tonic::async_trait]
+    impl TgtSelect for MemTarget {
+        type AllStream = ReceiverStream<Result<TgtPair, Status>>;
+
+        async fn all(&self, _: Request<TgtAll>) -> Result<Response<Self::AllStream>, Status> {
+            let pairs: Vec<TgtPair> = self
+                .rows
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(key, row)| TgtPair {
+                    key: key.clone(),
+                    check: row.1.clone(),
+                })
+                .collect();
+            let (tx, rx) = tokio::sync::mpsc::channel(1);
+            tokio::spawn(async move {
+                for pair in pairs {
+                    if tx.send(Ok(pair)).await.is_err() {
+                        return;
+                    }
+                }
+            });
+            Ok(Response::new(ReceiverStream::new(rx)))
+        }
+    }
+}